@@ -0,0 +1,312 @@
+use iri_string::types::IriAbsoluteStr;
+
+use crate::indexable::Indexable;
+
+use super::{Bubble, ExcludePolicy, Expansion, Reach};
+
+/// A bubble in an expansion that admits a URI, and through which of its
+/// indexables.
+#[derive(Debug)]
+pub struct Admission<'a> {
+    pub(super) bubble: &'a Bubble,
+    pub(super) reach: &'a Reach,
+    pub(super) indexables: Vec<&'a Indexable>,
+}
+
+/// An exclude of one bubble that covers an indexable of another. Whether it
+/// applies depends on how the bubbles were reached.
+#[derive(Debug)]
+pub struct ExcludeConflict<'a> {
+    pub(super) exclude: &'a Indexable,
+    pub(super) exclude_reach: &'a Reach,
+    pub(super) indexable: &'a Indexable,
+    pub(super) indexable_reach: &'a Reach,
+    pub(super) applies: bool,
+}
+
+impl Expansion<'_> {
+    /// The bubbles through which a URI is part of the expansion. Each bubble's
+    /// indexables are subject to its own excludes, those of the bubbles it
+    /// was reached through, and any that propagate.
+    pub fn admitting(&self, uri: &IriAbsoluteStr) -> Vec<Admission<'_>> {
+        self.bubbles()
+            .filter(|(_, reach)| !self.is_excluded(reach, uri))
+            .map(|(bubble, reach)| Admission {
+                bubble,
+                reach,
+                indexables: bubble
+                    .indexables()
+                    .iter()
+                    .filter(|indexable| indexable.admits(uri))
+                    .collect(),
+            })
+            .filter(|admission| !admission.indexables.is_empty())
+            .collect()
+    }
+
+    pub fn admits(&self, uri: &IriAbsoluteStr) -> bool {
+        !self.admitting(uri).is_empty()
+    }
+
+    /// Do the excludes that apply to the bubble reached by `reach` cover the
+    /// URI?
+    pub fn is_excluded(&self, reach: &Reach, uri: &IriAbsoluteStr) -> bool {
+        self.bubbles().any(|(bubble, excluding)| {
+            excluding.excludes_apply_to(reach)
+                && bubble.excludes().iter().any(|exclude| exclude.admits(uri))
+        })
+    }
+
+    /// Excludes of one bubble that cover indexables of another, so curators
+    /// can see where bubbles disagree.
+    pub fn conflicts(&self) -> Vec<ExcludeConflict<'_>> {
+        let mut conflicts = Vec::new();
+        for (exclude, exclude_reach) in self.excludes() {
+            for (indexable, indexable_reach) in self.indexables() {
+                if exclude_reach.via == indexable_reach.via {
+                    continue;
+                }
+                if exclude.admits(indexable.uri()) || indexable.admits(exclude.uri()) {
+                    conflicts.push(ExcludeConflict {
+                        exclude,
+                        exclude_reach,
+                        indexable,
+                        indexable_reach,
+                        applies: exclude_reach.excludes_apply_to(indexable_reach),
+                    });
+                }
+            }
+        }
+        conflicts
+    }
+}
+
+impl Reach {
+    /// What the referencing bubbles decided about this bubble's excludes,
+    /// along the whole chain from the root: a bubble can't widen what the
+    /// bubbles above it allowed. Any `ignore` ignores them, and they only
+    /// propagate to the root if every reference lets them.
+    pub fn exclude_policy(&self) -> ExcludePolicy {
+        let policies = self.via.iter().map(|reference| reference.exclude_policy());
+        if policies
+            .clone()
+            .any(|policy| policy == ExcludePolicy::Ignore)
+        {
+            ExcludePolicy::Ignore
+        } else if !self.via.is_empty()
+            && policies
+                .clone()
+                .all(|policy| policy == ExcludePolicy::Propagate)
+        {
+            ExcludePolicy::Propagate
+        } else {
+            ExcludePolicy::Own
+        }
+    }
+
+    // whether excludes of the bubble reached this way apply to the bubble
+    // reached by `other`: to itself and what it expands into, and to the
+    // bubbles above it for as far as each reference on the way propagates
+    // them, unless any reference ignores them
+    fn excludes_apply_to(&self, other: &Reach) -> bool {
+        if self.exclude_policy() == ExcludePolicy::Ignore {
+            return false;
+        }
+        let propagated = self
+            .via
+            .iter()
+            .rev()
+            .take_while(|reference| reference.exclude_policy() == ExcludePolicy::Propagate)
+            .count();
+        let applies_below = &self.via[..self.via.len() - propagated];
+        other.via.len() >= applies_below.len()
+            && applies_below
+                .iter()
+                .zip(&other.via)
+                .all(|(a, b)| a.uri() == b.uri())
+    }
+}
+
+impl<'a> Admission<'a> {
+    pub fn bubble(&self) -> &'a Bubble {
+        self.bubble
+    }
+
+    pub fn reach(&self) -> &'a Reach {
+        self.reach
+    }
+
+    pub fn indexables(&self) -> &[&'a Indexable] {
+        &self.indexables
+    }
+}
+
+impl<'a> ExcludeConflict<'a> {
+    pub fn exclude(&self) -> &'a Indexable {
+        self.exclude
+    }
+
+    pub fn exclude_reach(&self) -> &'a Reach {
+        self.exclude_reach
+    }
+
+    pub fn indexable(&self) -> &'a Indexable {
+        self.indexable
+    }
+
+    pub fn indexable_reach(&self) -> &'a Reach {
+        self.indexable_reach
+    }
+
+    /// Whether the exclude wins. If not, the indexable's bubble ignores it.
+    pub fn applies(&self) -> bool {
+        self.applies
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use iri_string::types::IriAbsoluteString;
+
+    use crate::bubble::Limits;
+
+    use super::*;
+
+    fn bubble(title: &str, body: &str) -> Bubble {
+        Bubble::parse_html(&format!(
+            "<html><head><title>{title}</title></head><body>{body}</body></html>"
+        ))
+        .unwrap()
+    }
+
+    // we include example.com and expand into a friend's bubble, which
+    // includes another.com but excludes parts of both
+    fn expansion(root: &Bubble) -> Expansion<'_> {
+        Expansion::resolve(root, &Limits::default(), |_| {
+            Ok::<_, String>(bubble(
+                "Friend",
+                r#"<a class="bubble-search-site" href="https://another.com">Another</a>
+                   <a class="bubble-exclude-path" href="https://another.com/ads">Ads</a>
+                   <a class="bubble-exclude-path" href="https://example.com/old">Old</a>"#,
+            ))
+        })
+    }
+
+    fn root(policy: &str) -> Bubble {
+        bubble(
+            "Root",
+            &format!(
+                r#"<a class="bubble-search-site" href="https://example.com">Example</a>
+                   <a class="bubble-exclude-path" href="https://another.com/spam">Spam</a>
+                   <a class="bubble-expand" href="https://friend.org/bubble" data-excludes="{policy}">Friend</a>"#
+            ),
+        )
+    }
+
+    fn admitted(expansion: &Expansion, uris: &[&str]) -> Vec<bool> {
+        uris.iter()
+            .map(|uri| expansion.admits(&uri.parse::<IriAbsoluteString>().unwrap()))
+            .collect()
+    }
+
+    const URIS: [&str; 4] = [
+        "https://another.com/spam",
+        "https://another.com/ads",
+        "https://example.com/old",
+        "https://example.com/new",
+    ];
+
+    #[test]
+    fn test_parent_excludes_apply_to_child() {
+        let root = root("own");
+        let expansion = expansion(&root);
+        assert_eq!(admitted(&expansion, &URIS), vec![false, false, true, true]);
+    }
+
+    #[test]
+    fn test_propagated_excludes_apply_to_parent() {
+        let root = root("propagate");
+        let expansion = expansion(&root);
+        assert_eq!(admitted(&expansion, &URIS), vec![false, false, false, true]);
+    }
+
+    #[test]
+    fn test_ignored_excludes() {
+        let root = root("ignore");
+        let expansion = expansion(&root);
+        assert_eq!(admitted(&expansion, &URIS), vec![false, true, true, true]);
+    }
+
+    // the friend expands into a bubble of its own, telling us its excludes
+    // propagate
+    fn nested_expansion(root: &Bubble) -> Expansion<'_> {
+        Expansion::resolve(root, &Limits::default(), |reference| {
+            Ok::<_, String>(match reference.uri().as_str() {
+                "https://friend.org/bubble" => bubble(
+                    "Friend",
+                    r#"<a class="bubble-search-site" href="https://another.com">Another</a>
+                       <a class="bubble-expand" href="https://nested.org/bubble" data-excludes="propagate">Nested</a>"#,
+                ),
+                _ => bubble(
+                    "Nested",
+                    r#"<a class="bubble-exclude-path" href="https://example.com/old">Old</a>
+                       <a class="bubble-exclude-path" href="https://another.com/ads">Ads</a>"#,
+                ),
+            })
+        })
+    }
+
+    #[test]
+    fn test_nested_propagate_stops_at_own_reference() {
+        let root = root("own");
+        let expansion = nested_expansion(&root);
+        assert_eq!(
+            expansion.expanded()[1].reach().exclude_policy(),
+            ExcludePolicy::Own
+        );
+        // up to the friend, which let them propagate, but not to the root
+        assert_eq!(admitted(&expansion, &URIS), vec![false, false, true, true]);
+    }
+
+    #[test]
+    fn test_nested_propagate_under_propagate_reaches_root() {
+        let root = root("propagate");
+        let expansion = nested_expansion(&root);
+        assert_eq!(admitted(&expansion, &URIS), vec![false, false, false, true]);
+    }
+
+    #[test]
+    fn test_nested_propagate_under_ignore() {
+        let root = root("ignore");
+        let expansion = nested_expansion(&root);
+        assert_eq!(
+            expansion.expanded()[1].reach().exclude_policy(),
+            ExcludePolicy::Ignore
+        );
+        assert_eq!(admitted(&expansion, &URIS), vec![false, true, true, true]);
+    }
+
+    #[test]
+    fn test_conflicts() {
+        let root = root("own");
+        let expansion = expansion(&root);
+        let conflicts = expansion
+            .conflicts()
+            .iter()
+            .map(|conflict| {
+                (
+                    conflict.exclude().uri().as_str(),
+                    conflict.indexable().uri().as_str(),
+                    conflict.applies(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            conflicts,
+            vec![
+                ("https://another.com/spam", "https://another.com", true),
+                ("https://example.com/old", "https://example.com", false),
+            ]
+        );
+    }
+}
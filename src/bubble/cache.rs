@@ -0,0 +1,137 @@
+use std::{collections::BTreeMap, fs, io::ErrorKind, path::Path};
+
+use iri_string::types::IriAbsoluteStr;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::Integrity;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum BubbleCacheError {
+    #[error("Could not access bubble cache file: {0}")]
+    Io(String),
+    #[error("Could not read bubble cache file: {0}")]
+    Format(String),
+}
+
+impl From<std::io::Error> for BubbleCacheError {
+    fn from(error: std::io::Error) -> Self {
+        BubbleCacheError::Io(error.to_string())
+    }
+}
+
+impl From<serde_json::Error> for BubbleCacheError {
+    fn from(error: serde_json::Error) -> Self {
+        BubbleCacheError::Format(error.to_string())
+    }
+}
+
+/// Copies of pinned bubbles, keyed by their integrity hash. When a pinned
+/// bubble has since changed or can't be fetched, the copy matching the pin
+/// is used instead. The copy of each bubble last fetched is kept by URL too,
+/// so an expansion can be rebuilt without the network.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct BubbleCache {
+    bubbles: BTreeMap<String, String>,
+    // caches written before fetched copies were kept don't have them
+    #[serde(default)]
+    fetched: BTreeMap<String, String>,
+}
+
+impl BubbleCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load the cache from disk. A missing file is an empty cache.
+    pub fn load(path: &Path) -> Result<Self, BubbleCacheError> {
+        match fs::read(path) {
+            Ok(data) => Ok(serde_json::from_slice(&data)?),
+            Err(error) if error.kind() == ErrorKind::NotFound => Ok(Self::new()),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), BubbleCacheError> {
+        let temporary = path.with_extension("tmp");
+        fs::write(&temporary, serde_json::to_vec(self)?)?;
+        fs::rename(&temporary, path)?;
+        Ok(())
+    }
+
+    /// The cached bubble document with this hash, if any. A copy that
+    /// doesn't match, say after editing the file, is ignored.
+    pub fn get(&self, integrity: &Integrity) -> Option<&str> {
+        self.bubbles
+            .get(&integrity.to_string())
+            .map(String::as_str)
+            .filter(|html| integrity.verifies(html.as_bytes()))
+    }
+
+    /// Keep a bubble document that was verified against the hash.
+    pub fn insert(&mut self, integrity: &Integrity, html: &str) {
+        self.bubbles.insert(integrity.to_string(), html.to_string());
+    }
+
+    /// The bubble document last fetched from this URL, if any.
+    pub fn get_fetched(&self, uri: &IriAbsoluteStr) -> Option<&str> {
+        self.fetched.get(uri.as_str()).map(String::as_str)
+    }
+
+    /// Keep the bubble document just fetched from this URL, replacing any
+    /// earlier copy.
+    pub fn insert_fetched(&mut self, uri: &IriAbsoluteStr, html: &str) {
+        self.fetched.insert(uri.to_string(), html.to_string());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::integrity::Algorithm;
+    use super::*;
+
+    #[test]
+    fn test_get() {
+        let integrity = Integrity::of(Algorithm::Sha256, b"<html>pinned</html>");
+        let mut cache = BubbleCache::new();
+        assert_eq!(cache.get(&integrity), None);
+        cache.insert(&integrity, "<html>pinned</html>");
+        assert_eq!(cache.get(&integrity), Some("<html>pinned</html>"));
+    }
+
+    #[test]
+    fn test_get_ignores_mismatch() {
+        let integrity = Integrity::of(Algorithm::Sha256, b"<html>pinned</html>");
+        let mut cache = BubbleCache::new();
+        cache.insert(&integrity, "<html>edited</html>");
+        assert_eq!(cache.get(&integrity), None);
+    }
+
+    #[test]
+    fn test_get_fetched() {
+        let uri = IriAbsoluteStr::new("https://friend.org/bubble").unwrap();
+        let mut cache = BubbleCache::new();
+        assert_eq!(cache.get_fetched(uri), None);
+        cache.insert_fetched(uri, "<html>old</html>");
+        cache.insert_fetched(uri, "<html>new</html>");
+        assert_eq!(cache.get_fetched(uri), Some("<html>new</html>"));
+    }
+
+    #[test]
+    fn test_load_without_fetched_copies() {
+        let cache = serde_json::from_str::<BubbleCache>(r#"{"bubbles":{}}"#).unwrap();
+        assert!(cache.fetched.is_empty());
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let path = std::env::temp_dir().join("bubble-cache-test.json");
+        let integrity = Integrity::of(Algorithm::Sha256, b"<html>pinned</html>");
+        let mut cache = BubbleCache::new();
+        cache.insert(&integrity, "<html>pinned</html>");
+        cache.save(&path).unwrap();
+        let loaded = BubbleCache::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.get(&integrity), Some("<html>pinned</html>"));
+    }
+}
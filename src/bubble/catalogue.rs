@@ -0,0 +1,137 @@
+use iri_string::types::{IriAbsoluteStr, IriAbsoluteString};
+
+use crate::topic::{Topic, TopicRelation, Wikidata};
+
+use super::Bubble;
+
+/// Bubbles known locally, with the Wikidata items their topics are placed
+/// in, so that a user searching one bubble can be offered broader or
+/// narrower ones.
+#[derive(Debug)]
+pub struct Catalogue {
+    wikidata: Wikidata,
+    bubbles: Vec<(IriAbsoluteString, Bubble)>,
+}
+
+/// A bubble in the catalogue whose topic relates to the one asked about.
+#[derive(Debug, PartialEq)]
+pub struct RelatedBubble<'a> {
+    pub(super) uri: &'a IriAbsoluteStr,
+    pub(super) bubble: &'a Bubble,
+    pub(super) relation: TopicRelation,
+}
+
+impl Catalogue {
+    pub fn new(wikidata: Wikidata) -> Self {
+        Self {
+            wikidata,
+            bubbles: Vec::new(),
+        }
+    }
+
+    pub fn with_bubble(mut self, uri: IriAbsoluteString, bubble: Bubble) -> Self {
+        self.insert(uri, bubble);
+        self
+    }
+
+    pub fn insert(&mut self, uri: IriAbsoluteString, bubble: Bubble) {
+        self.bubbles.push((uri, bubble));
+    }
+
+    pub fn wikidata(&self) -> &Wikidata {
+        &self.wikidata
+    }
+
+    /// The bubbles about the same, a broader or a narrower topic, in that
+    /// order and otherwise in catalogue order.
+    pub fn related_bubbles(&self, topic: &Topic) -> Vec<RelatedBubble<'_>> {
+        let mut related = self
+            .bubbles
+            .iter()
+            .filter_map(|(uri, bubble)| {
+                Some(RelatedBubble {
+                    uri,
+                    bubble,
+                    relation: self.wikidata.relation(bubble.topic(), topic)?,
+                })
+            })
+            .collect::<Vec<_>>();
+        related.sort_by_key(|related| related.relation);
+        related
+    }
+}
+
+impl<'a> RelatedBubble<'a> {
+    pub fn uri(&self) -> &'a IriAbsoluteStr {
+        self.uri
+    }
+
+    pub fn bubble(&self) -> &'a Bubble {
+        self.bubble
+    }
+
+    /// How the related bubble's topic relates to the one asked about.
+    pub fn relation(&self) -> TopicRelation {
+        self.relation
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Rust, and Tokio as part of it
+    const DUMP: &str = r#"[
+{"type":"item","id":"Q575650","labels":{"en":{"language":"en","value":"Rust"}},"sitelinks":{"enwiki":{"site":"enwiki","title":"Rust (programming language)"}}},
+{"type":"item","id":"Q1","labels":{"en":{"language":"en","value":"Tokio"}},"claims":{"P361":[{"mainsnak":{"snaktype":"value","property":"P361","datavalue":{"value":{"entity-type":"item","numeric-id":575650,"id":"Q575650"},"type":"wikibase-entityid"}}}]}}
+]"#;
+
+    fn bubble(title: &str, topic: &str) -> Bubble {
+        Bubble::parse_html(&format!(
+            "<html><head><title>{title}</title></head><body>{topic}</body></html>"
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_related_bubbles() {
+        let rust = r#"<a class="bubble-wikidata-topic" href="https://www.wikidata.org/wiki/Q575650">Rust</a>"#;
+        let catalogue = Catalogue::new(Wikidata::read(DUMP.as_bytes()).unwrap())
+            .with_bubble(
+                "https://example.com/tokio".parse().unwrap(),
+                bubble(
+                    "Tokio",
+                    r#"<a class="bubble-wikidata-topic" href="https://www.wikidata.org/wiki/Q1">Tokio</a>"#,
+                ),
+            )
+            .with_bubble(
+                "https://example.com/cooking".parse().unwrap(),
+                bubble("Cooking", ""),
+            )
+            .with_bubble(
+                "https://example.com/rust".parse().unwrap(),
+                bubble(
+                    "Rust",
+                    r#"<a class="bubble-wikipedia-topic" href="https://en.wikipedia.org/wiki/Rust_(programming_language)">Rust</a>"#,
+                ),
+            );
+        let related = |topic: &Topic| {
+            catalogue
+                .related_bubbles(topic)
+                .iter()
+                .map(|related| (related.bubble().title().to_string(), related.relation()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            related(bubble("Rust", rust).topic()),
+            vec![
+                ("Rust".to_string(), TopicRelation::Equal),
+                ("Tokio".to_string(), TopicRelation::Narrower),
+            ]
+        );
+        let tokio = catalogue.related_bubbles(catalogue.bubbles[0].1.topic());
+        assert_eq!(tokio[1].relation(), TopicRelation::Broader);
+        assert_eq!(tokio[1].uri().as_str(), "https://example.com/rust");
+        assert!(related(bubble("Cooking", "").topic()).is_empty());
+    }
+}
@@ -0,0 +1,153 @@
+use crate::indexable::Indexable;
+
+use super::{Bubble, BubbleReference};
+
+/// What changed between two versions of a bubble. Indexables and excludes are
+/// compared by URI and scope, expanded bubbles by URI; a changed label alone
+/// isn't a change.
+#[derive(Debug, Default, PartialEq)]
+pub struct BubbleDiff<'a> {
+    pub(super) added_indexables: Vec<&'a Indexable>,
+    pub(super) removed_indexables: Vec<&'a Indexable>,
+    pub(super) added_excludes: Vec<&'a Indexable>,
+    pub(super) removed_excludes: Vec<&'a Indexable>,
+    pub(super) added_expands: Vec<&'a BubbleReference>,
+    pub(super) removed_expands: Vec<&'a BubbleReference>,
+}
+
+impl Bubble {
+    pub fn diff<'a>(&'a self, previous: &'a Bubble) -> BubbleDiff<'a> {
+        BubbleDiff {
+            added_indexables: missing_from(&self.indexables, &previous.indexables),
+            removed_indexables: missing_from(&previous.indexables, &self.indexables),
+            added_excludes: missing_from(&self.excludes, &previous.excludes),
+            removed_excludes: missing_from(&previous.excludes, &self.excludes),
+            added_expands: references_missing_from(&self.expands, &previous.expands),
+            removed_expands: references_missing_from(&previous.expands, &self.expands),
+        }
+    }
+}
+
+impl<'a> BubbleDiff<'a> {
+    pub fn added_indexables(&self) -> &[&'a Indexable] {
+        &self.added_indexables
+    }
+
+    pub fn removed_indexables(&self) -> &[&'a Indexable] {
+        &self.removed_indexables
+    }
+
+    pub fn added_excludes(&self) -> &[&'a Indexable] {
+        &self.added_excludes
+    }
+
+    pub fn removed_excludes(&self) -> &[&'a Indexable] {
+        &self.removed_excludes
+    }
+
+    pub fn added_expands(&self) -> &[&'a BubbleReference] {
+        &self.added_expands
+    }
+
+    pub fn removed_expands(&self) -> &[&'a BubbleReference] {
+        &self.removed_expands
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self == &BubbleDiff::default()
+    }
+}
+
+fn missing_from<'a>(indexables: &'a [Indexable], other: &[Indexable]) -> Vec<&'a Indexable> {
+    indexables
+        .iter()
+        .filter(|indexable| {
+            !other
+                .iter()
+                .any(|o| o.uri() == indexable.uri() && o.scope() == indexable.scope())
+        })
+        .collect()
+}
+
+fn references_missing_from<'a>(
+    references: &'a [BubbleReference],
+    other: &[BubbleReference],
+) -> Vec<&'a BubbleReference> {
+    references
+        .iter()
+        .filter(|reference| !other.iter().any(|o| o.uri() == reference.uri()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bubble(body: &str) -> Bubble {
+        Bubble::parse_html(&format!(
+            "<html><head><title>Test</title></head><body>{body}</body></html>"
+        ))
+        .unwrap()
+    }
+
+    fn uris<'a>(indexables: &[&'a Indexable]) -> Vec<&'a str> {
+        indexables
+            .iter()
+            .map(|indexable| indexable.uri().as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_diff() {
+        let previous = bubble(
+            r#"<a class="bubble-search-site" href="https://example.com">Example</a>
+               <a class="bubble-search-page" href="https://another.com/a">A</a>
+               <a class="bubble-exclude-path" href="https://example.com/old">Old</a>"#,
+        );
+        let current = bubble(
+            r#"<a class="bubble-search-site" href="https://example.com">Renamed</a>
+               <a class="bubble-search-site" href="https://another.com/a">A</a>
+               <a class="bubble-exclude-path" href="https://example.com/new">New</a>"#,
+        );
+        let diff = current.diff(&previous);
+        assert_eq!(uris(diff.added_indexables()), vec!["https://another.com/a"]);
+        assert_eq!(
+            uris(diff.removed_indexables()),
+            vec!["https://another.com/a"]
+        );
+        assert_eq!(uris(diff.added_excludes()), vec!["https://example.com/new"]);
+        assert_eq!(
+            uris(diff.removed_excludes()),
+            vec!["https://example.com/old"]
+        );
+    }
+
+    #[test]
+    fn test_diff_expands() {
+        let previous = bubble(
+            r#"<a class="bubble-expand" href="https://friend.com/bubble">Friend</a>
+               <a class="bubble-expand" href="https://old.com/bubble">Old</a>"#,
+        );
+        let current = bubble(
+            r#"<a class="bubble-expand" href="https://friend.com/bubble" data-weight="0.5">Renamed</a>
+               <a class="bubble-expand" href="https://new.com/bubble">New</a>"#,
+        );
+        let diff = current.diff(&previous);
+        let uris = |references: &[&BubbleReference]| {
+            references
+                .iter()
+                .map(|reference| reference.uri().to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(uris(diff.added_expands()), vec!["https://new.com/bubble"]);
+        assert_eq!(uris(diff.removed_expands()), vec!["https://old.com/bubble"]);
+        assert!(diff.added_indexables().is_empty());
+        assert!(!diff.is_empty());
+    }
+
+    #[test]
+    fn test_diff_unchanged() {
+        let html = r#"<a class="bubble-search-site" href="https://example.com">Example</a>"#;
+        assert!(bubble(html).diff(&bubble(html)).is_empty());
+    }
+}
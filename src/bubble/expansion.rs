@@ -0,0 +1,422 @@
+use std::{
+    collections::{HashSet, VecDeque},
+    fmt::Display,
+};
+
+use iri_string::types::{IriAbsoluteStr, IriAbsoluteString};
+use thiserror::Error;
+
+use crate::indexable::Indexable;
+
+use super::{Bubble, BubbleReference, Limits};
+
+/// Why a referenced bubble isn't part of an expansion.
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum ExpansionError {
+    #[error("Could not load bubble: {0}")]
+    Load(String),
+    #[error("More than {0} expands away from the root")]
+    TooDeep(u32),
+    #[error("More than {0} expanded bubbles")]
+    TooManyBubbles(usize),
+    #[error("More than {0} indexables in the expansion")]
+    TooManyIndexables(usize),
+}
+
+/// A bubble together with the bubbles it expands into, directly or through
+/// other expanded bubbles.
+#[derive(Debug)]
+pub struct Expansion<'a> {
+    root: &'a Bubble,
+    root_reach: Reach,
+    expanded: Vec<ExpandedBubble>,
+    // references that weren't followed, with the reason
+    unresolved: Vec<(IriAbsoluteString, ExpansionError)>,
+}
+
+#[derive(Debug)]
+pub struct ExpandedBubble {
+    uri: IriAbsoluteString,
+    bubble: Bubble,
+    reach: Reach,
+}
+
+/// How a bubble was reached from the root of an expansion.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reach {
+    // 0 for the root, 1 for bubbles it expands into directly
+    pub(super) depth: u32,
+    // the weights of the references followed, multiplied
+    pub(super) weight: f64,
+    // the references followed, starting at the root
+    pub(super) via: Vec<BubbleReference>,
+}
+
+impl<'a> Expansion<'a> {
+    /// The bubble on its own, without following any references.
+    pub fn new(root: &'a Bubble) -> Self {
+        Self {
+            root,
+            root_reach: Reach::root(),
+            expanded: Vec::new(),
+            unresolved: Vec::new(),
+        }
+    }
+
+    /// Follow references breadth first within the limits, loading each
+    /// bubble once. A bubble reached by several routes keeps the shortest.
+    /// `load` gets the reference followed, so it can check any integrity
+    /// pin.
+    pub fn resolve<E: Display>(
+        root: &'a Bubble,
+        limits: &Limits,
+        mut load: impl FnMut(&BubbleReference) -> Result<Bubble, E>,
+    ) -> Self {
+        let mut expansion = Self::new(root);
+        let mut indexables = root.indexables().len() + root.excludes().len();
+        let mut seen = HashSet::new();
+        let mut queue = VecDeque::from(references(root, &Reach::root(), &mut seen));
+        while let Some((uri, reach)) = queue.pop_front() {
+            if reach.depth > limits.max_depth {
+                let error = ExpansionError::TooDeep(limits.max_depth);
+                expansion.unresolved.push((uri, error));
+                continue;
+            }
+            if expansion.expanded.len() >= limits.max_bubbles {
+                let error = ExpansionError::TooManyBubbles(limits.max_bubbles);
+                expansion.unresolved.push((uri, error));
+                continue;
+            }
+            let reference = reach.via.last().expect("expanded bubbles are referenced");
+            let bubble = match load(reference) {
+                Ok(bubble) => bubble,
+                Err(error) => {
+                    let error = ExpansionError::Load(error.to_string());
+                    expansion.unresolved.push((uri, error));
+                    continue;
+                }
+            };
+            indexables += bubble.indexables().len() + bubble.excludes().len();
+            if indexables > limits.max_indexables {
+                indexables -= bubble.indexables().len() + bubble.excludes().len();
+                let error = ExpansionError::TooManyIndexables(limits.max_indexables);
+                expansion.unresolved.push((uri, error));
+                continue;
+            }
+            queue.extend(references(&bubble, &reach, &mut seen));
+            expansion
+                .expanded
+                .push(ExpandedBubble { uri, bubble, reach });
+        }
+        expansion
+    }
+
+    pub fn root(&self) -> &Bubble {
+        self.root
+    }
+
+    pub fn expanded(&self) -> &[ExpandedBubble] {
+        &self.expanded
+    }
+
+    pub fn unresolved(&self) -> &[(IriAbsoluteString, ExpansionError)] {
+        &self.unresolved
+    }
+
+    /// Every bubble in the expansion, the root first.
+    pub fn bubbles(&self) -> impl Iterator<Item = (&Bubble, &Reach)> {
+        std::iter::once((self.root, &self.root_reach)).chain(
+            self.expanded
+                .iter()
+                .map(|expanded| (&expanded.bubble, &expanded.reach)),
+        )
+    }
+
+    /// Every indexable in the expansion, with how its bubble was reached.
+    pub fn indexables(&self) -> impl Iterator<Item = (&Indexable, &Reach)> {
+        self.bubbles().flat_map(|(bubble, reach)| {
+            bubble
+                .indexables()
+                .iter()
+                .map(move |indexable| (indexable, reach))
+        })
+    }
+
+    /// Every exclude in the expansion, with how its bubble was reached.
+    pub fn excludes(&self) -> impl Iterator<Item = (&Indexable, &Reach)> {
+        self.bubbles().flat_map(|(bubble, reach)| {
+            bubble
+                .excludes()
+                .iter()
+                .map(move |exclude| (exclude, reach))
+        })
+    }
+}
+
+// the references of a bubble that haven't been seen yet
+fn references(
+    bubble: &Bubble,
+    reach: &Reach,
+    seen: &mut HashSet<IriAbsoluteString>,
+) -> Vec<(IriAbsoluteString, Reach)> {
+    bubble
+        .expands()
+        .iter()
+        .filter(|reference| seen.insert(reference.uri().to_owned()))
+        .map(|reference| {
+            (
+                reference.uri().to_owned(),
+                Reach {
+                    depth: reach.depth + 1,
+                    weight: reach.weight * reference.weight(),
+                    via: reach.via.iter().chain([reference]).cloned().collect(),
+                },
+            )
+        })
+        .collect()
+}
+
+impl ExpandedBubble {
+    pub fn uri(&self) -> &IriAbsoluteStr {
+        &self.uri
+    }
+
+    pub fn bubble(&self) -> &Bubble {
+        &self.bubble
+    }
+
+    pub fn reach(&self) -> &Reach {
+        &self.reach
+    }
+}
+
+impl Reach {
+    /// The reach of the bubble an expansion starts from.
+    pub fn root() -> Self {
+        Self {
+            depth: 0,
+            weight: 1.0,
+            via: Vec::new(),
+        }
+    }
+
+    pub fn depth(&self) -> u32 {
+        self.depth
+    }
+
+    pub fn weight(&self) -> f64 {
+        self.weight
+    }
+
+    pub fn via(&self) -> &[BubbleReference] {
+        &self.via
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn bubble(title: &str, expands: &[(&str, &str)]) -> Bubble {
+        bubble_with(title, expands, "")
+    }
+
+    fn bubble_with(title: &str, expands: &[(&str, &str)], body: &str) -> Bubble {
+        let links = expands
+            .iter()
+            .map(|(uri, weight)| {
+                format!(r#"<a class="bubble-expand" href="{uri}" data-weight="{weight}">{uri}</a>"#)
+            })
+            .collect::<String>();
+        Bubble::parse_html(&format!(
+            "<html><head><title>{title}</title></head><body>{links}{body}</body></html>"
+        ))
+        .unwrap()
+    }
+
+    fn bubbles() -> HashMap<&'static str, Bubble> {
+        HashMap::from([
+            (
+                "https://a.example/",
+                bubble_with(
+                    "A",
+                    &[
+                        ("https://b.example/", "0.5"),
+                        ("https://missing.example/", "1"),
+                    ],
+                    r#"<a class="bubble-search-site" href="https://a.example/site">A</a>"#,
+                ),
+            ),
+            (
+                "https://b.example/",
+                bubble_with(
+                    "B",
+                    &[("https://c.example/", "1")],
+                    r#"<a class="bubble-exclude-path" href="https://b.example/spam">Spam</a>"#,
+                ),
+            ),
+            (
+                "https://c.example/",
+                bubble_with(
+                    "C",
+                    &[],
+                    r#"<a class="bubble-search-site" href="https://c.example/site">C</a>"#,
+                ),
+            ),
+        ])
+    }
+
+    fn resolve(root: &Bubble, max_depth: u32) -> Expansion<'_> {
+        resolve_within(
+            root,
+            &Limits {
+                max_depth,
+                ..Limits::default()
+            },
+        )
+    }
+
+    fn resolve_within<'a>(root: &'a Bubble, limits: &Limits) -> Expansion<'a> {
+        let mut bubbles = bubbles();
+        Expansion::resolve(root, limits, |reference| {
+            bubbles.remove(reference.uri().as_str()).ok_or("not found")
+        })
+    }
+
+    fn reached(expansion: &Expansion) -> Vec<(String, u32, f64)> {
+        expansion
+            .bubbles()
+            .map(|(bubble, reach)| (bubble.title().to_string(), reach.depth(), reach.weight()))
+            .collect()
+    }
+
+    #[test]
+    fn test_resolve_follows_references() {
+        let root = bubble("Root", &[("https://a.example/", "1")]);
+        let expansion = resolve(&root, 3);
+        assert_eq!(
+            reached(&expansion),
+            vec![
+                ("Root".to_string(), 0, 1.0),
+                ("A".to_string(), 1, 1.0),
+                ("B".to_string(), 2, 0.5),
+                ("C".to_string(), 3, 0.5),
+            ]
+        );
+        // a bubble that can't be loaded doesn't stop the others
+        assert_eq!(
+            expansion.unresolved(),
+            &[(
+                "https://missing.example/".parse().unwrap(),
+                ExpansionError::Load("not found".to_string())
+            )]
+        );
+    }
+
+    #[test]
+    fn test_resolve_records_provenance() {
+        let root = bubble("Root", &[("https://a.example/", "1")]);
+        let expansion = resolve(&root, 3);
+        let via = expansion
+            .indexables()
+            .chain(expansion.excludes())
+            .map(|(indexable, reach)| {
+                (
+                    indexable.uri().as_str(),
+                    reach
+                        .via()
+                        .iter()
+                        .map(|reference| reference.uri().as_str())
+                        .collect::<Vec<_>>(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            via,
+            vec![
+                ("https://a.example/site", vec!["https://a.example/"]),
+                (
+                    "https://c.example/site",
+                    vec![
+                        "https://a.example/",
+                        "https://b.example/",
+                        "https://c.example/"
+                    ]
+                ),
+                (
+                    "https://b.example/spam",
+                    vec!["https://a.example/", "https://b.example/"]
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_resolve_stops_at_max_depth() {
+        let root = bubble("Root", &[("https://a.example/", "1")]);
+        let expansion = resolve(&root, 1);
+        assert_eq!(
+            reached(&expansion),
+            vec![("Root".to_string(), 0, 1.0), ("A".to_string(), 1, 1.0)]
+        );
+    }
+
+    #[test]
+    fn test_resolve_reports_limits() {
+        let root = bubble("Root", &[("https://a.example/", "1")]);
+        let expansion = resolve(&root, 1);
+        assert_eq!(
+            expansion.unresolved()[0],
+            (
+                "https://b.example/".parse().unwrap(),
+                ExpansionError::TooDeep(1)
+            )
+        );
+
+        let limits = Limits {
+            max_bubbles: 1,
+            ..Limits::default()
+        };
+        let expansion = resolve_within(&root, &limits);
+        assert_eq!(expansion.expanded().len(), 1);
+        assert_eq!(
+            expansion.unresolved()[0].1,
+            ExpansionError::TooManyBubbles(1)
+        );
+
+        // A fits, but the one more indexable B brings doesn't
+        let limits = Limits {
+            max_indexables: 1,
+            ..Limits::default()
+        };
+        let expansion = resolve_within(&root, &limits);
+        assert_eq!(expansion.expanded().len(), 1);
+        assert_eq!(
+            expansion.unresolved()[0],
+            (
+                "https://b.example/".parse().unwrap(),
+                ExpansionError::TooManyIndexables(1)
+            )
+        );
+    }
+
+    #[test]
+    fn test_resolve_keeps_shortest_route() {
+        let root = bubble(
+            "Root",
+            &[("https://a.example/", "1"), ("https://c.example/", "0.25")],
+        );
+        let expansion = resolve(&root, 3);
+        assert_eq!(
+            reached(&expansion),
+            vec![
+                ("Root".to_string(), 0, 1.0),
+                ("A".to_string(), 1, 1.0),
+                ("C".to_string(), 1, 0.25),
+                ("B".to_string(), 2, 0.5),
+            ]
+        );
+    }
+}
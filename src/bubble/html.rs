@@ -1,21 +1,40 @@
+use iri_string::types::IriAbsoluteStr;
 use scraper::{error::SelectorErrorKind, Html, Selector};
 use thiserror::Error;
 
 use crate::{
+    document::element_lang,
     indexable::{Indexable, IndexableParseError},
-    topic::{Topic, TopicParseError},
+    topic::{Topic, TopicCategory, TopicParseError},
 };
 
-use super::model::{Bubble, BubbleReference};
+use super::{
+    model::{Bubble, BubbleReference, ExcludePolicy},
+    BubbleMetadata, Integrity, Limits, FORMAT_VERSION,
+};
 
 #[derive(Error, Debug, PartialEq, Eq)]
 pub enum BubbleParseError {
     #[error("Missing title element in HTML document")]
     MissingTitle,
+    #[error("Invalid bubble-version: {0}")]
+    InvalidVersion(String),
+    #[error("Bubble format version {0} is newer than this version understands")]
+    UnsupportedVersion(u32),
+    #[error("Bubble document larger than {0} bytes")]
+    TooLarge(usize),
+    #[error("More than {0} links in bubble document")]
+    TooManyAnchors(usize),
     #[error("Missing href in expands: {0}")]
     MissingHref(String),
     #[error("Invalid URL in expands: {0}")]
     InvalidUrl(String),
+    #[error("Invalid data-weight in expands: {0}")]
+    InvalidWeight(String),
+    #[error("Invalid data-excludes in expands: {0}")]
+    InvalidExcludePolicy(String),
+    #[error("Invalid data-integrity in expands: {0}")]
+    InvalidIntegrity(String),
     #[error("Could not parse topic: {0}")]
     TopicError(#[from] TopicParseError),
     #[error("Could not parse indexables: {0}")]
@@ -32,18 +51,48 @@ impl<'a> From<SelectorErrorKind<'a>> for BubbleParseError {
 
 impl Bubble {
     pub fn parse_html(document: &str) -> Result<Self, BubbleParseError> {
+        Self::parse_html_with_limits(document, &Limits::default())
+    }
+
+    /// Parse a bubble, refusing documents beyond the size limits before
+    /// doing any real work on them. Indexables and expands with URLs we
+    /// wouldn't fetch are left out and listed in `rejected`. Excludes and
+    /// topics are never fetched, so they may point anywhere.
+    pub fn parse_html_with_limits(
+        document: &str,
+        limits: &Limits,
+    ) -> Result<Self, BubbleParseError> {
+        if document.len() > limits.max_document_bytes {
+            return Err(BubbleParseError::TooLarge(limits.max_document_bytes));
+        }
         let document = Html::parse_document(document);
+        if document.select(&Selector::parse("a")?).count() > limits.max_anchors {
+            return Err(BubbleParseError::TooManyAnchors(limits.max_anchors));
+        }
+        let document = Self::upgrade_document(document)?;
         let title = Self::parse_title(&document)?;
-        let indexables = Indexable::parse_indexables(&document)?;
+        let mut indexables = Indexable::parse_indexables(&document)?;
         let excludes = Indexable::parse_excludes(&document)?;
-        let expands = Self::make_expands(&document)?;
+        let mut expands = Self::make_expands(&document)?;
+        let mut rejected = Vec::new();
+        let mut allowed = |uri: &IriAbsoluteStr| match limits.urls.check(uri) {
+            Ok(()) => true,
+            Err(error) => {
+                rejected.push((uri.to_owned(), error));
+                false
+            }
+        };
+        indexables.retain(|indexable| allowed(indexable.uri()));
+        expands.retain(|reference| allowed(reference.uri()));
 
         Ok(Bubble {
             title,
+            metadata: BubbleMetadata::parse_html(&document)?,
             topic: Topic::parse_html(&document)?,
             expands,
             indexables,
             excludes,
+            rejected,
         })
     }
 
@@ -79,20 +128,208 @@ impl Bubble {
                     .value()
                     .attr("href")
                     .ok_or_else(|| BubbleParseError::MissingHref(expands.html()))?;
+                let weight = match expands.value().attr("data-weight") {
+                    Some(weight) => weight
+                        .trim()
+                        .parse::<f64>()
+                        .ok()
+                        .filter(|weight| weight.is_finite() && *weight > 0.0)
+                        .ok_or_else(|| BubbleParseError::InvalidWeight(expands.html()))?,
+                    None => 1.0,
+                };
+                let excludes = expands
+                    .value()
+                    .attr("data-excludes")
+                    .map(|policy| {
+                        ExcludePolicy::parse(policy)
+                            .ok_or_else(|| BubbleParseError::InvalidExcludePolicy(expands.html()))
+                    })
+                    .transpose()?
+                    .unwrap_or_default();
+                let integrity = expands
+                    .value()
+                    .attr("data-integrity")
+                    .map(|integrity| {
+                        Integrity::parse(integrity)
+                            .ok_or_else(|| BubbleParseError::InvalidIntegrity(expands.html()))
+                    })
+                    .transpose()?;
                 Ok(BubbleReference {
                     uri: href
                         .try_into()
                         .map_err(|_| BubbleParseError::InvalidUrl(expands.html()))?,
                     label: expands.text().collect(),
+                    lang: element_lang(expands),
+                    weight,
+                    excludes,
+                    integrity,
                 })
             })
             .collect::<Result<Vec<_>, BubbleParseError>>()
     }
 }
 
+impl Bubble {
+    /// Write the bubble as an HTML page that parses back to the same bubble.
+    /// Only what the model keeps is written, so layout and anything else on
+    /// the original page is lost.
+    pub fn to_html(&self) -> String {
+        let metadata = &self.metadata;
+        let mut html = String::from("<!DOCTYPE html>\n");
+        match metadata.lang() {
+            Some(lang) => html += &format!("<html lang=\"{}\">\n", escape(lang)),
+            None => html += "<html>\n",
+        }
+        html += "<head>\n<meta charset=\"utf-8\">\n";
+        html += &format!("<meta name=\"bubble-version\" content=\"{FORMAT_VERSION}\">\n");
+        html += &format!("<title>{}</title>\n", escape(&self.title));
+        if let Some(description) = metadata.description() {
+            html += &format!(
+                "<meta name=\"description\" content=\"{}\">\n",
+                escape(description)
+            );
+        }
+        if let Some(license) = metadata.license() {
+            html += &format!(
+                "<link rel=\"license\" href=\"{}\">\n",
+                escape(license.as_str())
+            );
+        }
+        html += "</head>\n<body>\n";
+        html += &format!("<h1>{}</h1>\n", escape(&self.title));
+        for curator in metadata.curators() {
+            let name = escape(curator.name());
+            match curator.uri() {
+                Some(uri) => {
+                    html += &format!(
+                        "<p><a class=\"h-card\" href=\"{}\">{name}</a></p>\n",
+                        escape(uri.as_str())
+                    )
+                }
+                None => html += &format!("<p><span class=\"h-card\">{name}</span></p>\n"),
+            }
+        }
+        if let Some(updated) = metadata.updated() {
+            html += &format!(
+                "<p>Updated <time class=\"dt-updated\" datetime=\"{0}\">{0}</time></p>\n",
+                escape(updated)
+            );
+        }
+        // labels in the page's language inherit it
+        let lang = |lang: Option<&str>| {
+            lang.filter(|lang| Some(*lang) != metadata.lang())
+                .map(|lang| ("lang", lang.to_string()))
+        };
+        html += "<ul>\n";
+        for reference in self.topic.references() {
+            // one link per label
+            for label in reference.labels() {
+                let (class, mut attributes) = match reference.category() {
+                    TopicCategory::Wikipedia => ("bubble-wikipedia-topic", vec![]),
+                    TopicCategory::Wikidata => ("bubble-wikidata-topic", vec![]),
+                    category => (
+                        "bubble-topic",
+                        vec![("data-scheme", category.scheme().to_string())],
+                    ),
+                };
+                attributes.extend(lang(label.lang()));
+                html += &anchor(class, reference.uri(), &attributes, label.text());
+            }
+        }
+        let indexables = self
+            .indexables
+            .iter()
+            .map(|indexable| ("search", indexable))
+            .chain(self.excludes.iter().map(|exclude| ("exclude", exclude)))
+            .collect::<Vec<_>>();
+        for (kind, indexable) in &indexables {
+            if indexable.section().is_none() {
+                html += &indexable_anchor(kind, indexable, lang(indexable.lang()));
+            }
+        }
+        for reference in &self.expands {
+            let mut attributes = Vec::new();
+            if reference.weight() != 1.0 {
+                attributes.push(("data-weight", reference.weight().to_string()));
+            }
+            if reference.exclude_policy() != ExcludePolicy::default() {
+                let policy = reference.exclude_policy().as_str().to_string();
+                attributes.push(("data-excludes", policy));
+            }
+            if let Some(integrity) = reference.integrity() {
+                attributes.push(("data-integrity", integrity.to_string()));
+            }
+            attributes.extend(lang(reference.lang()));
+            html += &anchor(
+                "bubble-expand",
+                reference.uri(),
+                &attributes,
+                reference.label(),
+            );
+        }
+        html += "</ul>\n";
+        for section in self.sections() {
+            html += &format!("<h2>{}</h2>\n<ul>\n", escape(section));
+            for (kind, indexable) in &indexables {
+                if indexable.section() == Some(section) {
+                    html += &indexable_anchor(kind, indexable, lang(indexable.lang()));
+                }
+            }
+            html += "</ul>\n";
+        }
+        html += "</body>\n</html>\n";
+        html
+    }
+}
+
+// kind is search or exclude
+fn indexable_anchor(kind: &str, indexable: &Indexable, lang: Option<(&str, String)>) -> String {
+    let scope = indexable.scope().as_str();
+    let mut attributes = Vec::new();
+    if let Some(refresh) = indexable.refresh() {
+        attributes.push(("data-refresh", refresh.as_str().to_string()));
+    }
+    if indexable.boost() != 1.0 {
+        attributes.push(("data-boost", indexable.boost().to_string()));
+    }
+    if let Some(note) = indexable.note() {
+        attributes.push(("data-note", note.to_string()));
+    }
+    if !indexable.tags().is_empty() {
+        attributes.push(("data-tags", indexable.tags().join(", ")));
+    }
+    attributes.extend(lang);
+    anchor(
+        &format!("bubble-{kind}-{scope}"),
+        indexable.uri(),
+        &attributes,
+        indexable.label(),
+    )
+}
+
+fn anchor(class: &str, uri: &IriAbsoluteStr, attributes: &[(&str, String)], label: &str) -> String {
+    let attributes = attributes
+        .iter()
+        .map(|(name, value)| format!(" {name}=\"{}\"", escape(value)))
+        .collect::<String>();
+    format!(
+        "<li><a class=\"{class}\" href=\"{}\"{attributes}>{}</a></li>\n",
+        escape(uri.as_str()),
+        escape(label)
+    )
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
 #[cfg(test)]
 mod tests {
     use crate::{
+        bubble::{UnsafeUrl, UrlPolicy},
         indexable::Scope,
         topic::{TopicCategory, TopicReference},
     };
@@ -183,6 +420,10 @@ mod tests {
             vec![BubbleReference {
                 uri: "https://another.org/my-bubble".parse().unwrap(),
                 label: "Another bubble!".to_string(),
+                lang: None,
+                weight: 1.0,
+                excludes: ExcludePolicy::Own,
+                integrity: None,
             }]
         );
         assert_eq!(
@@ -226,4 +467,179 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn test_parse_expand_weight() {
+        let html = r#"<html>
+            <head><title>Test Title</title></head>
+            <body>
+                <a class="bubble-expand" href="https://another.org/my-bubble" data-weight="0.5">Another</a>
+            </body>
+        </html>"#;
+        let bubble = Bubble::parse_html(html).unwrap();
+        assert_eq!(bubble.expands[0].weight(), 0.5);
+
+        let html = html.replace("0.5", "none");
+        assert!(matches!(
+            Bubble::parse_html(&html),
+            Err(BubbleParseError::InvalidWeight(_))
+        ));
+    }
+
+    #[test]
+    fn test_parse_expand_exclude_policy() {
+        let html = r#"<html>
+            <head><title>Test Title</title></head>
+            <body>
+                <a class="bubble-expand" href="https://another.org/my-bubble" data-excludes="ignore">Another</a>
+            </body>
+        </html>"#;
+        let bubble = Bubble::parse_html(html).unwrap();
+        assert_eq!(bubble.expands[0].exclude_policy(), ExcludePolicy::Ignore);
+
+        let html = html.replace("ignore", "sometimes");
+        assert!(matches!(
+            Bubble::parse_html(&html),
+            Err(BubbleParseError::InvalidExcludePolicy(_))
+        ));
+    }
+
+    #[test]
+    fn test_parse_expand_integrity() {
+        let html = r#"<html>
+            <head><title>Test Title</title></head>
+            <body>
+                <a class="bubble-expand" href="https://another.org/my-bubble" data-integrity="sha256-47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU=">Another</a>
+            </body>
+        </html>"#;
+        let bubble = Bubble::parse_html(html).unwrap();
+        assert!(bubble.expands[0].integrity().unwrap().verifies(b""));
+
+        let html = html.replace("sha256-", "md5-");
+        assert!(matches!(
+            Bubble::parse_html(&html),
+            Err(BubbleParseError::InvalidIntegrity(_))
+        ));
+    }
+
+    #[test]
+    fn test_parse_metadata() {
+        let html = r#"<html lang="en">
+            <head>
+                <title>Test Title</title>
+                <meta name="description" content="Testing">
+                <link rel="license" href="https://creativecommons.org/licenses/by/4.0/">
+            </head>
+            <body><a class="h-card" href="https://alice.example">Alice</a></body>
+        </html>"#;
+        let bubble = Bubble::parse_html(html).unwrap();
+        assert_eq!(bubble.metadata().description(), Some("Testing"));
+        assert_eq!(bubble.metadata().curators()[0].name(), "Alice");
+        assert_eq!(bubble.metadata().lang(), Some("en"));
+    }
+
+    #[test]
+    fn test_to_html_round_trip() {
+        let html = r#"<html lang="en">
+            <head>
+                <title>Tom &amp; Jerry</title>
+                <meta name="description" content="Cats &quot;and&quot; mice">
+                <link rel="license" href="https://creativecommons.org/licenses/by/4.0/">
+                <link rel="author" href="https://alice.example" title="Alice">
+            </head>
+            <body>
+                <span class="h-card">Bob</span>
+                <time class="dt-updated" datetime="2024-05-17">May</time>
+                <a class="bubble-wikipedia-topic" href="https://en.wikipedia.org/wiki/Cat">Cat</a>
+                <a class="bubble-wikidata-topic" href="https://www.wikidata.org/wiki/Q146">Cat</a>
+                <a class="bubble-wikidata-topic" lang="nl" href="https://www.wikidata.org/wiki/Q146">Kat</a>
+                <a class="bubble-topic" data-scheme="lcsh" href="https://id.loc.gov/authorities/subjects/sh85020748">Cats</a>
+                <a class="bubble-search-page" href="https://example.com/a" title="Notes &amp; more" data-tags="a, b">A &lt;page&gt;</a>
+                <a class="bubble-search-site" href="https://example.com" data-refresh="daily" data-boost="2.5">Site</a>
+                <h2>Paths</h2>
+                <a class="bubble-search-path" href="https://example.com/b" hreflang="fr">Chemin</a>
+                <a class="bubble-exclude-path" href="https://example.com/b/ads">Ads</a>
+                <a class="bubble-expand" href="https://friend.org/bubble" data-weight="0.5" data-excludes="propagate" data-integrity="sha256-47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU=">Friend</a>
+            </body>
+        </html>"#;
+        let bubble = Bubble::parse_html(html).unwrap();
+        assert_eq!(bubble.topic().references()[1].labels().len(), 2);
+        assert_eq!(bubble.indexables()[2].lang(), Some("fr"));
+        assert_eq!(Bubble::parse_html(&bubble.to_html()).unwrap(), bubble);
+    }
+
+    #[test]
+    fn test_parse_limits() {
+        let html = r#"<html>
+            <head><title>Test Title</title></head>
+            <body>
+                <a class="bubble-search-site" href="https://example.com">Example</a>
+                <a class="bubble-search-site" href="https://example.org">Example</a>
+            </body>
+        </html>"#;
+        let limits = Limits {
+            max_anchors: 1,
+            ..Limits::default()
+        };
+        assert_eq!(
+            Bubble::parse_html_with_limits(html, &limits),
+            Err(BubbleParseError::TooManyAnchors(1))
+        );
+        let limits = Limits {
+            max_document_bytes: 100,
+            ..Limits::default()
+        };
+        assert_eq!(
+            Bubble::parse_html_with_limits(html, &limits),
+            Err(BubbleParseError::TooLarge(100))
+        );
+        assert!(Bubble::parse_html_with_limits(html, &Limits::default()).is_ok());
+    }
+
+    #[test]
+    fn test_parse_rejects_unsafe_urls() {
+        let html = r#"<html>
+            <head><title>Test Title</title></head>
+            <body>
+                <a class="bubble-search-site" href="http://127.0.0.1:8080/admin">Admin</a>
+                <a class="bubble-search-site" href="https://example.com">Example</a>
+                <a class="bubble-expand" href="http://localhost/bubble">Local</a>
+                <a class="bubble-expand" href="https://friend.org/bubble">Friend</a>
+                <a class="bubble-exclude-site" href="file:///etc">Files</a>
+            </body>
+        </html>"#;
+        // the rest of the bubble is still there
+        let bubble = Bubble::parse_html(html).unwrap();
+        assert_eq!(bubble.indexables().len(), 1);
+        assert_eq!(bubble.expands().len(), 1);
+        assert_eq!(bubble.excludes().len(), 1);
+        assert_eq!(
+            bubble.rejected(),
+            &[
+                (
+                    "http://127.0.0.1:8080/admin".parse().unwrap(),
+                    UnsafeUrl::PrivateAddress("http://127.0.0.1:8080/admin".to_string())
+                ),
+                (
+                    "http://localhost/bubble".parse().unwrap(),
+                    UnsafeUrl::PrivateAddress("http://localhost/bubble".to_string())
+                ),
+            ]
+        );
+        let limits = Limits {
+            urls: UrlPolicy {
+                allow_private_addresses: true,
+            },
+            ..Limits::default()
+        };
+        let bubble = Bubble::parse_html_with_limits(html, &limits).unwrap();
+        assert!(bubble.rejected().is_empty());
+        assert_eq!(bubble.indexables().len(), 2);
+
+        let html = html.replace("http://127.0.0.1:8080", "ftp://example.com");
+        assert!(matches!(
+            Bubble::parse_html(&html).unwrap().rejected()[0].1,
+            UnsafeUrl::Scheme(_)
+        ));
+    }
 }
@@ -0,0 +1,119 @@
+use std::fmt;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use sha2::{Digest, Sha256, Sha384, Sha512};
+
+/// A hash pinning the content of an expanded bubble, from `data-integrity`
+/// on the `bubble-expand` link. Written like subresource integrity:
+/// `sha256-` followed by the base64 digest.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Integrity {
+    algorithm: Algorithm,
+    digest: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Algorithm {
+    Sha256,
+    Sha384,
+    Sha512,
+}
+
+impl Integrity {
+    pub fn parse(value: &str) -> Option<Self> {
+        let (algorithm, digest) = value.trim().split_once('-')?;
+        let algorithm = match algorithm.to_ascii_lowercase().as_str() {
+            "sha256" => Algorithm::Sha256,
+            "sha384" => Algorithm::Sha384,
+            "sha512" => Algorithm::Sha512,
+            _ => return None,
+        };
+        let digest = STANDARD.decode(digest).ok()?;
+        (digest.len() == algorithm.len()).then_some(Self { algorithm, digest })
+    }
+
+    /// The hash of some content, for pinning a bubble as it is now.
+    pub fn of(algorithm: Algorithm, content: &[u8]) -> Self {
+        Self {
+            algorithm,
+            digest: algorithm.digest(content),
+        }
+    }
+
+    pub fn algorithm(&self) -> Algorithm {
+        self.algorithm
+    }
+
+    pub fn verifies(&self, content: &[u8]) -> bool {
+        self.algorithm.digest(content) == self.digest
+    }
+}
+
+impl fmt::Display for Integrity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let algorithm = match self.algorithm {
+            Algorithm::Sha256 => "sha256",
+            Algorithm::Sha384 => "sha384",
+            Algorithm::Sha512 => "sha512",
+        };
+        write!(f, "{algorithm}-{}", STANDARD.encode(&self.digest))
+    }
+}
+
+impl Algorithm {
+    // digest length in bytes
+    fn len(self) -> usize {
+        match self {
+            Algorithm::Sha256 => 32,
+            Algorithm::Sha384 => 48,
+            Algorithm::Sha512 => 64,
+        }
+    }
+
+    fn digest(self, content: &[u8]) -> Vec<u8> {
+        match self {
+            Algorithm::Sha256 => Sha256::digest(content).to_vec(),
+            Algorithm::Sha384 => Sha384::digest(content).to_vec(),
+            Algorithm::Sha512 => Sha512::digest(content).to_vec(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        // sha256 of the empty string
+        let integrity =
+            Integrity::parse("sha256-47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU=").unwrap();
+        assert_eq!(integrity.algorithm(), Algorithm::Sha256);
+        assert!(integrity.verifies(b""));
+        assert!(!integrity.verifies(b"changed"));
+        assert_eq!(
+            integrity.to_string(),
+            "sha256-47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU="
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        assert_eq!(Integrity::parse("md5-1B2M2Y8AsgTpgAmY7PhCfg=="), None);
+        assert_eq!(Integrity::parse("sha256-not base64"), None);
+        // a sha256 digest given as sha512
+        assert_eq!(
+            Integrity::parse("sha512-47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU="),
+            None
+        );
+        assert_eq!(Integrity::parse("47DEQpj8HBSa"), None);
+    }
+
+    #[test]
+    fn test_of() {
+        for algorithm in [Algorithm::Sha256, Algorithm::Sha384, Algorithm::Sha512] {
+            let integrity = Integrity::of(algorithm, b"<html></html>");
+            assert_eq!(Integrity::parse(&integrity.to_string()), Some(integrity));
+        }
+    }
+}
@@ -0,0 +1,33 @@
+use super::UrlPolicy;
+
+/// Bounds on what a bubble can make us do. Bubbles reached through
+/// `bubble-expand` are untrusted input, so a runaway or hostile expansion is
+/// cut off rather than exhausting the indexer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Limits {
+    // size of a single bubble document
+    pub max_document_bytes: usize,
+    // links of any kind in a single bubble document
+    pub max_anchors: usize,
+    // `bubble-expand` steps away from the root
+    pub max_depth: u32,
+    // bubbles loaded for one expansion, not counting the root
+    pub max_bubbles: usize,
+    // indexables and excludes across the whole expansion
+    pub max_indexables: usize,
+    // what indexables and expands may point at
+    pub urls: UrlPolicy,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_document_bytes: 1024 * 1024,
+            max_anchors: 10_000,
+            max_depth: 2,
+            max_bubbles: 50,
+            max_indexables: 10_000,
+            urls: UrlPolicy::default(),
+        }
+    }
+}
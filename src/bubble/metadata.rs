@@ -0,0 +1,255 @@
+use iri_string::types::{IriAbsoluteStr, IriAbsoluteString};
+use scraper::{ElementRef, Html, Selector};
+
+use crate::document::parse_iso_date;
+
+use super::BubbleParseError;
+
+/// What a bubble says about itself in its head, so it can be shared: who
+/// maintains it and under which terms it can be reused.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BubbleMetadata {
+    // from <meta name="description">
+    pub(super) description: Option<String>,
+    // from <link rel="author"> and h-cards
+    pub(super) curators: Vec<Curator>,
+    // from <link rel="license">
+    pub(super) license: Option<IriAbsoluteString>,
+    // from <html lang>
+    pub(super) lang: Option<String>,
+    // ISO 8601 date, from <time class="dt-updated">
+    pub(super) updated: Option<String>,
+}
+
+/// Someone maintaining a bubble. A curator has a name, a URL or both.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Curator {
+    pub(super) name: String,
+    pub(super) uri: Option<IriAbsoluteString>,
+}
+
+impl BubbleMetadata {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_description(mut self, description: &str) -> Self {
+        self.description = Some(description.to_string());
+        self
+    }
+
+    pub fn with_curator(mut self, curator: Curator) -> Self {
+        self.curators.push(curator);
+        self
+    }
+
+    pub fn with_license(mut self, license: IriAbsoluteString) -> Self {
+        self.license = Some(license);
+        self
+    }
+
+    pub fn with_lang(mut self, lang: &str) -> Self {
+        self.lang = Some(lang.to_string());
+        self
+    }
+
+    pub fn with_updated(mut self, updated: &str) -> Self {
+        self.updated = Some(updated.to_string());
+        self
+    }
+
+    pub fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+
+    pub fn curators(&self) -> &[Curator] {
+        &self.curators
+    }
+
+    pub fn license(&self) -> Option<&IriAbsoluteStr> {
+        self.license.as_deref()
+    }
+
+    pub fn lang(&self) -> Option<&str> {
+        self.lang.as_deref()
+    }
+
+    pub fn updated(&self) -> Option<&str> {
+        self.updated.as_deref()
+    }
+
+    pub(super) fn parse_html(html: &Html) -> Result<Self, BubbleParseError> {
+        let description = first_attribute(html, "head meta[name='description']", "content")?
+            .map(|description| description.trim().to_string())
+            .filter(|description| !description.is_empty());
+        let license = select(html, "link[rel~='license']")?
+            .next()
+            .and_then(|license| Self::parse_uri(license, "href"));
+        let lang = first_attribute(html, "html[lang]", "lang")?
+            .map(|lang| lang.trim().to_string())
+            .filter(|lang| !lang.is_empty());
+        // metadata is informative only, so values that don't parse are dropped
+        let updated = select(html, "time.dt-updated")?.next().and_then(|time| {
+            let text = time.text().collect::<String>();
+            parse_iso_date(time.value().attr("datetime").unwrap_or(&text))
+        });
+        Ok(Self {
+            description,
+            curators: Self::parse_curators(html)?,
+            license,
+            lang,
+            updated,
+        })
+    }
+
+    // authors linked from the head, then h-cards, each curator once
+    fn parse_curators(html: &Html) -> Result<Vec<Curator>, BubbleParseError> {
+        let mut curators = Vec::new();
+        for author in select(html, "link[rel~='author']")? {
+            curators.push(Curator {
+                name: author
+                    .value()
+                    .attr("title")
+                    .unwrap_or("")
+                    .trim()
+                    .to_string(),
+                uri: Self::parse_uri(author, "href"),
+            });
+        }
+        let name = Selector::parse(".p-name")?;
+        let url = Selector::parse(".u-url")?;
+        for card in select(html, ".h-card")? {
+            let name = card.select(&name).next().unwrap_or(card);
+            let uri = match card.select(&url).next() {
+                Some(url) => Self::parse_uri(url, "href"),
+                None => Self::parse_uri(card, "href"),
+            };
+            curators.push(Curator {
+                name: name.text().collect::<String>().trim().to_string(),
+                uri,
+            });
+        }
+        let mut unique = Vec::new();
+        for curator in curators {
+            let anonymous = curator.name.is_empty() && curator.uri.is_none();
+            if !anonymous && !unique.contains(&curator) {
+                unique.push(curator);
+            }
+        }
+        Ok(unique)
+    }
+
+    // a bubble has no base URL to resolve against, so relative links are dropped
+    fn parse_uri(element: ElementRef, attribute: &str) -> Option<IriAbsoluteString> {
+        element.value().attr(attribute)?.trim().try_into().ok()
+    }
+}
+
+impl Curator {
+    pub fn new(name: &str, uri: Option<IriAbsoluteString>) -> Self {
+        Self {
+            name: name.to_string(),
+            uri,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn uri(&self) -> Option<&IriAbsoluteStr> {
+        self.uri.as_deref()
+    }
+}
+
+fn select<'a>(
+    html: &'a Html,
+    selector: &str,
+) -> Result<impl Iterator<Item = ElementRef<'a>> + 'a, BubbleParseError> {
+    let selector = Selector::parse(selector)?;
+    Ok(html.select(&selector).collect::<Vec<_>>().into_iter())
+}
+
+fn first_attribute<'a>(
+    html: &'a Html,
+    selector: &str,
+    attribute: &str,
+) -> Result<Option<&'a str>, BubbleParseError> {
+    Ok(select(html, selector)?.find_map(|element| element.value().attr(attribute)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(html: &str) -> Result<BubbleMetadata, BubbleParseError> {
+        BubbleMetadata::parse_html(&Html::parse_document(html))
+    }
+
+    #[test]
+    fn test_parse_metadata() {
+        let metadata = parse(
+            r#"<html lang="nl">
+            <head>
+                <title>Web</title>
+                <meta name="description" content=" Everything about the web ">
+                <link rel="author" href="https://alice.example" title="Alice">
+                <link rel="license" href="https://creativecommons.org/licenses/by/4.0/">
+            </head>
+            <body>
+                <a class="h-card" href="https://alice.example">Alice</a>
+                <span class="h-card"><span class="p-name">Bob</span>
+                    <a class="u-url" href="https://bob.example">home</a></span>
+                <span class="h-card">Carol</span>
+                <time class="dt-updated" datetime="2024-05-17T10:00:00Z">May 17</time>
+            </body>
+        </html>"#,
+        )
+        .unwrap();
+        assert_eq!(metadata.description(), Some("Everything about the web"));
+        assert_eq!(
+            metadata.curators(),
+            &[
+                Curator::new("Alice", Some("https://alice.example".parse().unwrap())),
+                Curator::new("Bob", Some("https://bob.example".parse().unwrap())),
+                Curator::new("Carol", None),
+            ]
+        );
+        assert_eq!(
+            metadata.license().map(IriAbsoluteStr::as_str),
+            Some("https://creativecommons.org/licenses/by/4.0/")
+        );
+        assert_eq!(metadata.lang(), Some("nl"));
+        assert_eq!(metadata.updated(), Some("2024-05-17"));
+    }
+
+    #[test]
+    fn test_parse_no_metadata() {
+        let metadata = parse("<html><head><title>Web</title></head></html>").unwrap();
+        assert_eq!(metadata, BubbleMetadata::new());
+    }
+
+    #[test]
+    fn test_parse_invalid_metadata() {
+        let metadata = parse(
+            r#"<html>
+            <head>
+                <link rel="license" href="cc-by">
+                <link rel="author" href="/about" title="Alice">
+            </head>
+            <body>
+                <a class="h-card" href="/bob">Bob</a>
+                <a class="h-card" href="/about"></a>
+                <time class="dt-updated">last week</time>
+            </body>
+        </html>"#,
+        )
+        .unwrap();
+        assert_eq!(metadata.license(), None);
+        assert_eq!(
+            metadata.curators(),
+            &[Curator::new("Alice", None), Curator::new("Bob", None)]
+        );
+        assert_eq!(metadata.updated(), None);
+    }
+}
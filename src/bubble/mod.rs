@@ -1,2 +1,25 @@
+mod admission;
+mod cache;
+mod catalogue;
+mod diff;
+mod expansion;
 mod html;
+mod integrity;
+mod limits;
+mod metadata;
 mod model;
+mod policy;
+mod version;
+
+pub use admission::{Admission, ExcludeConflict};
+pub use cache::{BubbleCache, BubbleCacheError};
+pub use catalogue::{Catalogue, RelatedBubble};
+pub use diff::BubbleDiff;
+pub use expansion::{ExpandedBubble, Expansion, ExpansionError, Reach};
+pub use html::BubbleParseError;
+pub use integrity::{Algorithm, Integrity};
+pub use limits::Limits;
+pub use metadata::{BubbleMetadata, Curator};
+pub use model::{Bubble, BubbleReference, ExcludePolicy};
+pub use policy::{UnsafeUrl, UrlPolicy};
+pub use version::FORMAT_VERSION;
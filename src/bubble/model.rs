@@ -1,18 +1,154 @@
-use iri_string::types::IriAbsoluteString;
+use iri_string::types::{IriAbsoluteStr, IriAbsoluteString};
 
 use crate::{indexable::Indexable, topic::Topic};
 
-#[derive(Debug, PartialEq, Eq)]
+use super::{BubbleMetadata, Integrity, UnsafeUrl};
+
+#[derive(Debug, PartialEq)]
 pub struct Bubble {
     pub(super) title: String,
+    pub(super) metadata: BubbleMetadata,
     pub(super) topic: Topic,
     pub(super) expands: Vec<BubbleReference>,
     pub(super) indexables: Vec<Indexable>,
     pub(super) excludes: Vec<Indexable>,
+    // indexables and expands left out because we wouldn't fetch their URL
+    pub(super) rejected: Vec<(IriAbsoluteString, UnsafeUrl)>,
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct BubbleReference {
     pub(super) uri: IriAbsoluteString,
     pub(super) label: String,
+    // the language of the label, from `lang` or `hreflang`
+    pub(super) lang: Option<String>,
+    // how much the referenced bubble is trusted, from `data-weight`
+    pub(super) weight: f64,
+    pub(super) excludes: ExcludePolicy,
+    // the expected content of the referenced bubble, from `data-integrity`
+    pub(super) integrity: Option<Integrity>,
+}
+
+/// What the excludes of an expanded bubble do, from `data-excludes` on the
+/// `bubble-expand` link. Excludes of the referencing bubble always apply to
+/// the expanded one.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ExcludePolicy {
+    // they apply to the expanded bubble, and to the bubbles it expands into
+    #[default]
+    Own,
+    // they apply to the whole expansion, our own indexables included
+    Propagate,
+    // everything the expanded bubble includes is included
+    Ignore,
+}
+
+impl Bubble {
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    pub fn metadata(&self) -> &BubbleMetadata {
+        &self.metadata
+    }
+
+    pub fn topic(&self) -> &Topic {
+        &self.topic
+    }
+
+    pub fn expands(&self) -> &[BubbleReference] {
+        &self.expands
+    }
+
+    pub fn indexables(&self) -> &[Indexable] {
+        &self.indexables
+    }
+
+    pub fn excludes(&self) -> &[Indexable] {
+        &self.excludes
+    }
+
+    /// The URLs of indexables and expands that were left out, and why.
+    pub fn rejected(&self) -> &[(IriAbsoluteString, UnsafeUrl)] {
+        &self.rejected
+    }
+
+    /// The headings links are grouped under, in order of their first link.
+    pub fn sections(&self) -> Vec<&str> {
+        let mut sections = Vec::new();
+        for section in self
+            .indexables
+            .iter()
+            .chain(&self.excludes)
+            .filter_map(Indexable::section)
+        {
+            if !sections.contains(&section) {
+                sections.push(section);
+            }
+        }
+        sections
+    }
+
+    /// A URI is part of the bubble if any indexable admits it and no exclude
+    /// does.
+    pub fn admits(&self, uri: &IriAbsoluteStr) -> bool {
+        !self.admitting(uri).is_empty()
+    }
+
+    /// The indexables through which a URI is part of the bubble. This is empty
+    /// if an exclude applies.
+    pub fn admitting(&self, uri: &IriAbsoluteStr) -> Vec<&Indexable> {
+        if self.excludes.iter().any(|exclude| exclude.admits(uri)) {
+            return Vec::new();
+        }
+        self.indexables
+            .iter()
+            .filter(|indexable| indexable.admits(uri))
+            .collect()
+    }
+}
+
+impl BubbleReference {
+    pub fn uri(&self) -> &IriAbsoluteStr {
+        &self.uri
+    }
+
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    pub fn lang(&self) -> Option<&str> {
+        self.lang.as_deref()
+    }
+
+    pub fn weight(&self) -> f64 {
+        self.weight
+    }
+
+    pub fn exclude_policy(&self) -> ExcludePolicy {
+        self.excludes
+    }
+
+    pub fn integrity(&self) -> Option<&Integrity> {
+        self.integrity.as_ref()
+    }
+}
+
+impl ExcludePolicy {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ExcludePolicy::Own => "own",
+            ExcludePolicy::Propagate => "propagate",
+            ExcludePolicy::Ignore => "ignore",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "own" => Some(ExcludePolicy::Own),
+            "propagate" => Some(ExcludePolicy::Propagate),
+            "ignore" => Some(ExcludePolicy::Ignore),
+            _ => None,
+        }
+    }
 }
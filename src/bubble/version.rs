@@ -0,0 +1,197 @@
+use scraper::{Html, Node, Selector, StrTendril};
+
+use super::{Bubble, BubbleParseError};
+
+/// The bubble format `Bubble::to_html` writes, marked on the page with
+/// `<meta name="bubble-version">`. Pages without the marker are version 1.
+pub const FORMAT_VERSION: u32 = 1;
+
+/// How bubble pages change from the previous format version to `to`. When
+/// anchor classes are renamed, a migration lets older pages keep working.
+pub(super) struct Migration {
+    pub(super) to: u32,
+    // old class, new class
+    pub(super) classes: &'static [(&'static str, &'static str)],
+}
+
+// none yet: version 1 is the first versioned format
+const MIGRATIONS: &[Migration] = &[];
+
+impl Bubble {
+    /// Rewrite a bubble page in an older format version to the current one.
+    /// Unlike `to_html` this keeps the rest of the page as it was.
+    pub fn upgrade_html(document: &str) -> Result<String, BubbleParseError> {
+        upgrade(document, FORMAT_VERSION, MIGRATIONS)
+    }
+
+    // parse with the rules of the page's version, by upgrading it first
+    pub(super) fn upgrade_document(html: Html) -> Result<Html, BubbleParseError> {
+        let version = version(&html)?;
+        check_supported(version, FORMAT_VERSION)?;
+        if version == FORMAT_VERSION {
+            return Ok(html);
+        }
+        Ok(Html::parse_document(&upgrade(
+            &html.html(),
+            FORMAT_VERSION,
+            MIGRATIONS,
+        )?))
+    }
+}
+
+/// The format version a page declares.
+pub(super) fn version(html: &Html) -> Result<u32, BubbleParseError> {
+    let selector = Selector::parse("meta[name='bubble-version']")?;
+    match html.select(&selector).next() {
+        Some(meta) => meta
+            .value()
+            .attr("content")
+            .and_then(|version| version.trim().parse::<u32>().ok())
+            .filter(|version| *version >= 1)
+            .ok_or_else(|| BubbleParseError::InvalidVersion(meta.html())),
+        None => Ok(1),
+    }
+}
+
+fn check_supported(version: u32, current: u32) -> Result<(), BubbleParseError> {
+    if version > current {
+        return Err(BubbleParseError::UnsupportedVersion(version));
+    }
+    Ok(())
+}
+
+fn upgrade(
+    document: &str,
+    current: u32,
+    migrations: &[Migration],
+) -> Result<String, BubbleParseError> {
+    let mut html = Html::parse_document(document);
+    let version = version(&html)?;
+    check_supported(version, current)?;
+    let classes = migrations
+        .iter()
+        .filter(|migration| migration.to > version && migration.to <= current)
+        .flat_map(|migration| migration.classes)
+        .collect::<Vec<_>>();
+    let mut marked = false;
+    for node in html.tree.values_mut() {
+        let Node::Element(element) = node else {
+            continue;
+        };
+        let is_marker =
+            &*element.name.local == "meta" && element.attr("name") == Some("bubble-version");
+        for (name, value) in element.attrs.iter_mut() {
+            if is_marker && &*name.local == "content" {
+                *value = StrTendril::from(current.to_string());
+                marked = true;
+            }
+            if &*name.local == "class" {
+                // later migrations see the classes earlier ones renamed to
+                let renamed = value
+                    .split_whitespace()
+                    .map(|class| {
+                        let mut class = class;
+                        for (old, new) in &classes {
+                            if class == *old {
+                                class = new;
+                            }
+                        }
+                        class
+                    })
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                *value = StrTendril::from(renamed);
+            }
+        }
+    }
+    let mut document = html.html();
+    if !marked {
+        let marker = format!(r#"<meta name="bubble-version" content="{current}">"#);
+        match document
+            .find("<head")
+            .and_then(|head| Some(head + document[head..].find('>')? + 1))
+        {
+            Some(end_of_head) => document.insert_str(end_of_head, &marker),
+            None => document.insert_str(0, &marker),
+        }
+    }
+    Ok(document)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RENAMES: &[Migration] = &[
+        Migration {
+            to: 2,
+            classes: &[("bubble-site", "bubble-search-site")],
+        },
+        Migration {
+            to: 3,
+            classes: &[("bubble-search-site", "bubble-include-site")],
+        },
+    ];
+
+    #[test]
+    fn test_version() {
+        let version = |html: &str| version(&Html::parse_document(html));
+        assert_eq!(version("<html></html>"), Ok(1));
+        assert_eq!(
+            version(r#"<html><head><meta name="bubble-version" content="2"></head></html>"#),
+            Ok(2)
+        );
+        assert!(matches!(
+            version(r#"<html><head><meta name="bubble-version" content="one"></head></html>"#),
+            Err(BubbleParseError::InvalidVersion(_))
+        ));
+    }
+
+    #[test]
+    fn test_upgrade_renames_classes() {
+        let page = r#"<html><head><title>Old</title></head><body>
+            <p>Kept as is</p>
+            <a class="bubble-site important" href="https://example.com">Example</a>
+        </body></html>"#;
+        let upgraded = upgrade(page, 3, RENAMES).unwrap();
+        assert!(upgraded.contains(r#"<meta name="bubble-version" content="3">"#));
+        assert!(upgraded.contains(r#"class="bubble-include-site important""#));
+        assert!(upgraded.contains("<p>Kept as is</p>"));
+
+        // a page at version 2 only gets the later rename
+        let page = page.replace("bubble-site", "bubble-other").replace(
+            "<head>",
+            r#"<head><meta name="bubble-version" content="2">"#,
+        );
+        let upgraded = upgrade(&page, 3, RENAMES).unwrap();
+        assert!(upgraded.contains(r#"<meta name="bubble-version" content="3">"#));
+        assert!(upgraded.contains(r#"class="bubble-other important""#));
+    }
+
+    #[test]
+    fn test_newer_version_unsupported() {
+        let page = format!(
+            r#"<html><head><meta name="bubble-version" content="{}"><title>New</title></head></html>"#,
+            FORMAT_VERSION + 1
+        );
+        assert_eq!(
+            Bubble::parse_html(&page),
+            Err(BubbleParseError::UnsupportedVersion(FORMAT_VERSION + 1))
+        );
+        assert_eq!(
+            Bubble::upgrade_html(&page),
+            Err(BubbleParseError::UnsupportedVersion(FORMAT_VERSION + 1))
+        );
+    }
+
+    #[test]
+    fn test_upgrade_current_page() {
+        let page = "<html><head><title>Web</title></head><body></body></html>";
+        let upgraded = Bubble::upgrade_html(page).unwrap();
+        assert!(upgraded.contains(r#"<meta name="bubble-version" content="1">"#));
+        assert_eq!(
+            Bubble::parse_html(&upgraded).unwrap(),
+            Bubble::parse_html(page).unwrap()
+        );
+    }
+}
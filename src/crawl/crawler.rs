@@ -0,0 +1,604 @@
+use std::collections::{HashSet, VecDeque};
+
+use iri_string::types::{IriAbsoluteStr, IriAbsoluteString};
+use thiserror::Error;
+
+use crate::{
+    bubble::{Bubble, BubbleCache, BubbleParseError, Expansion, Limits, Reach, UnsafeUrl},
+    document::{Document, DocumentParseError},
+    indexable::{Indexable, Scope},
+    search::Index,
+};
+
+use super::fetch::{FetchError, Fetcher};
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum CrawlError {
+    #[error("Could not fetch page: {0}")]
+    Fetch(#[from] FetchError),
+    #[error("Could not parse page: {0}")]
+    Parse(#[from] DocumentParseError),
+    #[error("Could not parse bubble: {0}")]
+    Bubble(#[from] BubbleParseError),
+    #[error("Redirected outside the bubble to {0}")]
+    Redirected(String),
+    #[error("Refused to fetch: {0}")]
+    UnsafeUrl(#[from] UnsafeUrl),
+    #[error("Bubble does not match its integrity hash and no pinned copy is cached: {0}")]
+    IntegrityMismatch(String),
+    #[error("Bubble hasn't been fetched yet: {0}")]
+    NotCached(String),
+}
+
+/// What a crawl did. Failures don't stop a crawl, they're collected here.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct CrawlReport {
+    pub(crate) fetched: Vec<IriAbsoluteString>,
+    // fetched pages that are new or differ from what was indexed
+    pub(crate) changed: Vec<IriAbsoluteString>,
+    pub(crate) failed: Vec<(IriAbsoluteString, CrawlError)>,
+}
+
+impl CrawlReport {
+    pub fn fetched(&self) -> &[IriAbsoluteString] {
+        &self.fetched
+    }
+
+    pub fn changed(&self) -> &[IriAbsoluteString] {
+        &self.changed
+    }
+
+    pub fn failed(&self) -> &[(IriAbsoluteString, CrawlError)] {
+        &self.failed
+    }
+
+    pub fn merge(&mut self, other: CrawlReport) {
+        self.fetched.extend(other.fetched);
+        self.changed.extend(other.changed);
+        self.failed.extend(other.failed);
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Crawler {
+    max_pages: usize,
+    limits: Limits,
+}
+
+impl Default for Crawler {
+    fn default() -> Self {
+        Self {
+            max_pages: 1000,
+            limits: Limits::default(),
+        }
+    }
+}
+
+impl Crawler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The most pages a single crawl of an indexable fetches.
+    pub fn with_max_pages(mut self, max_pages: usize) -> Self {
+        self.max_pages = max_pages;
+        self
+    }
+
+    pub fn max_pages(&self) -> usize {
+        self.max_pages
+    }
+
+    /// Bounds on the bubbles fetched when expanding.
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    pub fn limits(&self) -> &Limits {
+        &self.limits
+    }
+
+    /// Crawl everything the bubble admits.
+    pub fn crawl_bubble(
+        &self,
+        fetcher: &dyn Fetcher,
+        index: &mut Index,
+        bubble: &Bubble,
+    ) -> CrawlReport {
+        let mut report = CrawlReport::default();
+        for indexable in bubble.indexables() {
+            report.merge(self.crawl(fetcher, index, bubble, indexable, indexable.uri()));
+        }
+        report
+    }
+
+    /// Fetch the bubbles this one expands into, within the limits. Pinned
+    /// bubbles that changed aren't expanded.
+    pub fn expand<'a>(&self, fetcher: &dyn Fetcher, bubble: &'a Bubble) -> Expansion<'a> {
+        self.expand_cached(fetcher, bubble, &mut BubbleCache::new())
+    }
+
+    /// Like `expand`, but a pinned bubble that changed or can't be fetched
+    /// is taken from the cache. Pinned bubbles that verify are cached, and
+    /// every bubble fetched is kept for `expand_offline`.
+    pub fn expand_cached<'a>(
+        &self,
+        fetcher: &dyn Fetcher,
+        bubble: &'a Bubble,
+        cache: &mut BubbleCache,
+    ) -> Expansion<'a> {
+        Expansion::resolve(
+            bubble,
+            &self.limits,
+            |reference| -> Result<Bubble, CrawlError> {
+                let uri = reference.uri();
+                self.limits.urls.check(uri)?;
+                let fetched = fetcher
+                    .fetch(uri)
+                    .map_err(CrawlError::from)
+                    .and_then(|page| {
+                        self.limits.urls.check(page.uri())?;
+                        Ok(page)
+                    });
+                let html = match (reference.integrity(), fetched) {
+                    (None, fetched) => {
+                        let page = fetched?;
+                        cache.insert_fetched(uri, page.html());
+                        page.html
+                    }
+                    (Some(integrity), Ok(page)) if integrity.verifies(page.html().as_bytes()) => {
+                        cache.insert(integrity, page.html());
+                        page.html
+                    }
+                    (Some(integrity), fetched) => match cache.get(integrity) {
+                        Some(html) => html.to_string(),
+                        None => {
+                            fetched?;
+                            return Err(CrawlError::IntegrityMismatch(uri.to_string()));
+                        }
+                    },
+                };
+                Ok(Bubble::parse_html_with_limits(&html, &self.limits)?)
+            },
+        )
+    }
+
+    /// Expand from the cache alone, as the bubbles were when last fetched,
+    /// without touching the network. Pinned bubbles are taken from their
+    /// pinned copy; bubbles never fetched are unresolved.
+    pub fn expand_offline<'a>(&self, bubble: &'a Bubble, cache: &BubbleCache) -> Expansion<'a> {
+        Expansion::resolve(
+            bubble,
+            &self.limits,
+            |reference| -> Result<Bubble, CrawlError> {
+                let uri = reference.uri();
+                let html = match reference.integrity() {
+                    Some(integrity) => cache.get(integrity),
+                    None => cache.get_fetched(uri),
+                }
+                .ok_or_else(|| CrawlError::NotCached(uri.to_string()))?;
+                Ok(Bubble::parse_html_with_limits(html, &self.limits)?)
+            },
+        )
+    }
+
+    /// Crawl everything the bubbles of the expansion admit, each within its
+    /// own bubble and the excludes that apply to it.
+    pub fn crawl_expansion(
+        &self,
+        fetcher: &dyn Fetcher,
+        index: &mut Index,
+        expansion: &Expansion,
+    ) -> CrawlReport {
+        let mut report = CrawlReport::default();
+        for (indexable, reach) in expansion.indexables() {
+            let admits = |uri: &IriAbsoluteStr| !expansion.is_excluded(reach, uri);
+            report.merge(self.crawl_from(
+                fetcher,
+                index,
+                &admits,
+                indexable,
+                indexable.uri(),
+                false,
+            ));
+        }
+        report
+    }
+
+    /// Crawl the part of an indexable reachable from `seed`, staying within
+    /// the bubble. Pages already in the index aren't fetched again, but their
+    /// links are still followed. Tombstoned pages are skipped.
+    pub fn crawl(
+        &self,
+        fetcher: &dyn Fetcher,
+        index: &mut Index,
+        bubble: &Bubble,
+        indexable: &Indexable,
+        seed: &IriAbsoluteStr,
+    ) -> CrawlReport {
+        self.crawl_from(
+            fetcher,
+            index,
+            &|uri| bubble.admits(uri),
+            indexable,
+            seed,
+            false,
+        )
+    }
+
+    /// Fetch all pages of an indexable again to pick up changes.
+    pub fn recrawl(
+        &self,
+        fetcher: &dyn Fetcher,
+        index: &mut Index,
+        bubble: &Bubble,
+        indexable: &Indexable,
+    ) -> CrawlReport {
+        self.crawl_from(
+            fetcher,
+            index,
+            &|uri| bubble.admits(uri),
+            indexable,
+            indexable.uri(),
+            true,
+        )
+    }
+
+    /// Like `recrawl`, for an indexable of a bubble in an expansion, within
+    /// the excludes that apply to it.
+    pub fn recrawl_expanded(
+        &self,
+        fetcher: &dyn Fetcher,
+        index: &mut Index,
+        expansion: &Expansion,
+        indexable: &Indexable,
+        reach: &Reach,
+    ) -> CrawlReport {
+        self.crawl_from(
+            fetcher,
+            index,
+            &|uri| !expansion.is_excluded(reach, uri),
+            indexable,
+            indexable.uri(),
+            true,
+        )
+    }
+
+    // `admits` decides what the bubble around the indexable lets in
+    pub(super) fn crawl_from(
+        &self,
+        fetcher: &dyn Fetcher,
+        index: &mut Index,
+        admits: &dyn Fn(&IriAbsoluteStr) -> bool,
+        indexable: &Indexable,
+        seed: &IriAbsoluteStr,
+        refetch: bool,
+    ) -> CrawlReport {
+        let mut report = CrawlReport::default();
+        let mut queue = VecDeque::from([seed.to_owned()]);
+        let mut seen = HashSet::from([seed.to_owned()]);
+
+        while let Some(uri) = queue.pop_front() {
+            if !indexable.admits(&uri) || !admits(&uri) || index.is_tombstoned(&uri) {
+                continue;
+            }
+            let links = match index.document(&uri) {
+                Some(document) if !refetch => document.links().to_vec(),
+                _ => {
+                    if report.fetched.len() >= self.max_pages {
+                        continue;
+                    }
+                    match self.fetch(fetcher, &uri, indexable, admits) {
+                        Ok(document) => {
+                            let links = document.links().to_vec();
+                            report.fetched.push(document.uri().to_owned());
+                            if index.document(document.uri()) != Some(&document) {
+                                report.changed.push(document.uri().to_owned());
+                                index.add(document);
+                            }
+                            links
+                        }
+                        Err(error) => {
+                            report.failed.push((uri, error));
+                            continue;
+                        }
+                    }
+                }
+            };
+            if *indexable.scope() == Scope::Page {
+                continue;
+            }
+            for link in links {
+                if seen.insert(link.clone()) {
+                    queue.push_back(link);
+                }
+            }
+        }
+        report
+    }
+
+    fn fetch(
+        &self,
+        fetcher: &dyn Fetcher,
+        uri: &IriAbsoluteStr,
+        indexable: &Indexable,
+        admits: &dyn Fn(&IriAbsoluteStr) -> bool,
+    ) -> Result<Document, CrawlError> {
+        // links on crawled pages are as untrusted as the bubbles themselves
+        self.limits.urls.check(uri)?;
+        let page = fetcher.fetch(uri)?;
+        self.limits.urls.check(page.uri())?;
+        // a redirect may lead out of the bubble
+        if page.uri() != uri && !(indexable.admits(page.uri()) && admits(page.uri())) {
+            return Err(CrawlError::Redirected(page.uri.to_string()));
+        }
+        Ok(Document::parse_html(page.uri, &page.html)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        bubble::{Algorithm, ExpansionError, Integrity},
+        crawl::fetch::tests::MemoryFetcher,
+    };
+
+    use super::*;
+
+    fn bubble(body: &str) -> Bubble {
+        Bubble::parse_html(&format!(
+            "<html><head><title>Test</title></head><body>{body}</body></html>"
+        ))
+        .unwrap()
+    }
+
+    fn site() -> MemoryFetcher {
+        MemoryFetcher::new(&[
+            (
+                "https://example.com",
+                r#"<a href="/a">A</a> <a href="/spam/b">B</a> <a href="https://another.com">C</a>"#,
+            ),
+            ("https://example.com/a", r#"<a href="/a/c">C</a>"#),
+            ("https://example.com/a/c", "Deep"),
+            ("https://example.com/spam/b", "Spam"),
+            ("https://another.com", "Elsewhere"),
+        ])
+    }
+
+    fn indexed(index: &Index) -> Vec<&str> {
+        index.uris().map(|uri| uri.as_str()).collect()
+    }
+
+    #[test]
+    fn test_crawl_site_stays_in_bubble() {
+        let bubble = bubble(
+            r#"<a class="bubble-search-site" href="https://example.com">Example</a>
+               <a class="bubble-exclude-path" href="https://example.com/spam">Spam</a>"#,
+        );
+        let mut index = Index::new();
+        let report = Crawler::new().crawl_bubble(&site(), &mut index, &bubble);
+        assert!(report.failed().is_empty());
+        assert_eq!(
+            indexed(&index),
+            vec![
+                "https://example.com",
+                "https://example.com/a",
+                "https://example.com/a/c"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_crawl_expansion() {
+        let mut fetcher = site();
+        fetcher.insert(
+            "https://friend.org/bubble",
+            r#"<html><head><title>Friend</title></head><body>
+                <a class="bubble-search-page" href="https://another.com">Another</a>
+            </body></html>"#,
+        );
+        let bubble = bubble(
+            r#"<a class="bubble-search-page" href="https://example.com/a">A</a>
+               <a class="bubble-expand" href="https://friend.org/bubble">Friend</a>
+               <a class="bubble-expand" href="https://gone.org/bubble">Gone</a>"#,
+        );
+        let crawler = Crawler::new().with_limits(Limits {
+            max_depth: 1,
+            ..Limits::default()
+        });
+        let expansion = crawler.expand(&fetcher, &bubble);
+        assert_eq!(expansion.expanded().len(), 1);
+        assert_eq!(
+            expansion.unresolved()[0].0.as_str(),
+            "https://gone.org/bubble"
+        );
+
+        let mut index = Index::new();
+        crawler.crawl_expansion(&fetcher, &mut index, &expansion);
+        assert_eq!(
+            indexed(&index),
+            vec!["https://another.com", "https://example.com/a"]
+        );
+    }
+
+    #[test]
+    fn test_expand_pinned_bubble() {
+        const PINNED: &str = "<html><head><title>Pinned</title></head><body></body></html>";
+        let mut fetcher = site();
+        fetcher.insert("https://friend.org/bubble", PINNED);
+        let integrity = Integrity::of(Algorithm::Sha256, PINNED.as_bytes());
+        let bubble = bubble(&format!(
+            r#"<a class="bubble-expand" href="https://friend.org/bubble" data-integrity="{integrity}">Friend</a>"#
+        ));
+        let crawler = Crawler::new();
+        let mut cache = BubbleCache::new();
+        let expansion = crawler.expand_cached(&fetcher, &bubble, &mut cache);
+        assert_eq!(expansion.expanded()[0].bubble().title(), "Pinned");
+        assert_eq!(cache.get(&integrity), Some(PINNED));
+
+        // the friend edits their bubble: we keep the pinned copy, or without
+        // one don't expand at all
+        fetcher.insert(
+            "https://friend.org/bubble",
+            "<html><head><title>Edited</title></head><body></body></html>",
+        );
+        let expansion = crawler.expand_cached(&fetcher, &bubble, &mut cache);
+        assert_eq!(expansion.expanded()[0].bubble().title(), "Pinned");
+        let expansion = crawler.expand(&fetcher, &bubble);
+        assert!(expansion.expanded().is_empty());
+        assert_eq!(
+            expansion.unresolved()[0].1,
+            ExpansionError::Load(
+                CrawlError::IntegrityMismatch("https://friend.org/bubble".to_string()).to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn test_expand_offline() {
+        const PINNED: &str = "<html><head><title>Pinned</title></head><body></body></html>";
+        let mut fetcher = site();
+        fetcher.insert(
+            "https://friend.org/bubble",
+            "<html><head><title>Friend</title></head><body></body></html>",
+        );
+        fetcher.insert("https://pinned.org/bubble", PINNED);
+        let integrity = Integrity::of(Algorithm::Sha256, PINNED.as_bytes());
+        let bubble = bubble(&format!(
+            r#"<a class="bubble-expand" href="https://friend.org/bubble">Friend</a>
+               <a class="bubble-expand" href="https://pinned.org/bubble" data-integrity="{integrity}">Pinned</a>
+               <a class="bubble-expand" href="https://gone.org/bubble">Gone</a>"#
+        ));
+        let crawler = Crawler::new();
+        let mut cache = BubbleCache::new();
+        crawler.expand_cached(&fetcher, &bubble, &mut cache);
+
+        let expansion = crawler.expand_offline(&bubble, &cache);
+        assert_eq!(
+            expansion
+                .expanded()
+                .iter()
+                .map(|expanded| expanded.bubble().title())
+                .collect::<Vec<_>>(),
+            vec!["Friend", "Pinned"]
+        );
+        assert_eq!(
+            expansion.unresolved(),
+            &[(
+                "https://gone.org/bubble".parse().unwrap(),
+                ExpansionError::Load(
+                    CrawlError::NotCached("https://gone.org/bubble".to_string()).to_string()
+                )
+            )]
+        );
+    }
+
+    #[test]
+    fn test_crawl_refuses_redirect_to_private_address() {
+        let mut fetcher = site();
+        fetcher.redirect("https://example.com/a", "http://169.254.169.254/");
+        fetcher.insert("http://169.254.169.254/", "Secrets");
+        let bubble = bubble(r#"<a class="bubble-search-page" href="https://example.com/a">A</a>"#);
+        let mut index = Index::new();
+        let report = Crawler::new().crawl_bubble(&fetcher, &mut index, &bubble);
+        assert_eq!(
+            report.failed(),
+            &[(
+                "https://example.com/a".parse().unwrap(),
+                CrawlError::UnsafeUrl(UnsafeUrl::PrivateAddress(
+                    "http://169.254.169.254/".to_string()
+                ))
+            )]
+        );
+        assert!(index.is_empty());
+    }
+
+    #[test]
+    fn test_crawl_page_does_not_follow_links() {
+        let bubble =
+            bubble(r#"<a class="bubble-search-page" href="https://example.com">Example</a>"#);
+        let mut index = Index::new();
+        Crawler::new().crawl_bubble(&site(), &mut index, &bubble);
+        assert_eq!(indexed(&index), vec!["https://example.com"]);
+    }
+
+    #[test]
+    fn test_crawl_max_pages() {
+        let bubble =
+            bubble(r#"<a class="bubble-search-site" href="https://example.com">Example</a>"#);
+        let mut index = Index::new();
+        let report = Crawler::new()
+            .with_max_pages(2)
+            .crawl_bubble(&site(), &mut index, &bubble);
+        assert_eq!(report.fetched().len(), 2);
+        assert_eq!(index.len(), 2);
+    }
+
+    #[test]
+    fn test_crawl_reports_failures() {
+        let bubble = bubble(
+            r#"<a class="bubble-search-page" href="https://example.com/missing">Missing</a>"#,
+        );
+        let mut index = Index::new();
+        let report = Crawler::new().crawl_bubble(&site(), &mut index, &bubble);
+        assert_eq!(
+            report.failed(),
+            &[(
+                "https://example.com/missing".parse().unwrap(),
+                CrawlError::Fetch(FetchError::Status(
+                    404,
+                    "https://example.com/missing".to_string()
+                ))
+            )]
+        );
+    }
+
+    #[test]
+    fn test_recrawl_detects_changes() {
+        let bubble =
+            bubble(r#"<a class="bubble-search-site" href="https://example.com">Example</a>"#);
+        let mut index = Index::new();
+        let crawler = Crawler::new();
+        crawler.crawl_bubble(&site(), &mut index, &bubble);
+
+        let mut changed = site();
+        changed.insert("https://example.com/a/c", "Changed");
+        let report = crawler.recrawl(&changed, &mut index, &bubble, &bubble.indexables()[0]);
+        assert_eq!(report.fetched().len(), 4);
+        assert_eq!(
+            report.changed(),
+            &["https://example.com/a/c"
+                .parse::<IriAbsoluteString>()
+                .unwrap()]
+        );
+    }
+
+    #[test]
+    fn test_crawl_skips_tombstoned_pages() {
+        let bubble =
+            bubble(r#"<a class="bubble-search-site" href="https://example.com">Example</a>"#);
+        let mut index = Index::new();
+        index.purge(
+            &"https://example.com/a"
+                .parse::<IriAbsoluteString>()
+                .unwrap(),
+        );
+        Crawler::new().crawl_bubble(&site(), &mut index, &bubble);
+        assert_eq!(
+            indexed(&index),
+            vec!["https://example.com", "https://example.com/spam/b"]
+        );
+    }
+
+    #[test]
+    fn test_crawl_skips_indexed_pages() {
+        let bubble =
+            bubble(r#"<a class="bubble-search-site" href="https://example.com">Example</a>"#);
+        let mut index = Index::new();
+        let crawler = Crawler::new();
+        crawler.crawl_bubble(&site(), &mut index, &bubble);
+        let report = crawler.crawl_bubble(&site(), &mut index, &bubble);
+        assert!(report.fetched().is_empty());
+    }
+}
@@ -0,0 +1,178 @@
+use std::{
+    io,
+    net::{SocketAddr, ToSocketAddrs},
+    time::Duration,
+};
+
+use iri_string::types::{IriAbsoluteStr, IriAbsoluteString};
+use thiserror::Error;
+
+use crate::bubble::UrlPolicy;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum FetchError {
+    #[error("HTTP status {0} for {1}")]
+    Status(u16, String),
+    #[error("Could not fetch {0}: {1}")]
+    Transport(String, String),
+    #[error("Not an HTML page: {0} ({1})")]
+    NotHtml(String, String),
+    #[error("Invalid URL after redirect: {0}")]
+    InvalidUrl(String),
+}
+
+/// A fetched page. The URI is where we ended up after any redirects.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Page {
+    pub(crate) uri: IriAbsoluteString,
+    pub(crate) html: String,
+}
+
+impl Page {
+    pub fn new(uri: IriAbsoluteString, html: String) -> Self {
+        Self { uri, html }
+    }
+
+    pub fn uri(&self) -> &IriAbsoluteStr {
+        &self.uri
+    }
+
+    pub fn html(&self) -> &str {
+        &self.html
+    }
+}
+
+/// Retrieves pages for the crawler. Abstracted so crawling can be tested
+/// without a network.
+pub trait Fetcher {
+    fn fetch(&self, uri: &IriAbsoluteStr) -> Result<Page, FetchError>;
+}
+
+pub struct HttpFetcher {
+    agent: ureq::Agent,
+}
+
+impl HttpFetcher {
+    pub fn new() -> Self {
+        Self::with_url_policy(UrlPolicy::default())
+    }
+
+    /// Host names are checked against the policy once resolved, including
+    /// on redirects, so DNS can't point us at a private address either.
+    pub fn with_url_policy(policy: UrlPolicy) -> Self {
+        let resolver = move |netloc: &str| -> io::Result<Vec<SocketAddr>> {
+            let addresses = netloc
+                .to_socket_addrs()?
+                .filter(|address| policy.allows_address(address.ip()))
+                .collect::<Vec<_>>();
+            if addresses.is_empty() {
+                return Err(io::Error::new(
+                    io::ErrorKind::PermissionDenied,
+                    format!("{netloc} resolves to a private address"),
+                ));
+            }
+            Ok(addresses)
+        };
+        Self {
+            agent: ureq::AgentBuilder::new()
+                .timeout(Duration::from_secs(30))
+                .user_agent(concat!("happy-search-bubbles/", env!("CARGO_PKG_VERSION")))
+                .resolver(resolver)
+                .build(),
+        }
+    }
+}
+
+impl Default for HttpFetcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Fetcher for HttpFetcher {
+    fn fetch(&self, uri: &IriAbsoluteStr) -> Result<Page, FetchError> {
+        let response = self
+            .agent
+            .get(uri.as_str())
+            .call()
+            .map_err(|error| match error {
+                ureq::Error::Status(status, _) => FetchError::Status(status, uri.to_string()),
+                ureq::Error::Transport(transport) => {
+                    FetchError::Transport(uri.to_string(), transport.to_string())
+                }
+            })?;
+        if response.content_type() != "text/html" {
+            return Err(FetchError::NotHtml(
+                uri.to_string(),
+                response.content_type().to_string(),
+            ));
+        }
+        let final_uri = response
+            .get_url()
+            .try_into()
+            .map_err(|_| FetchError::InvalidUrl(response.get_url().to_string()))?;
+        let html = response
+            .into_string()
+            .map_err(|error| FetchError::Transport(uri.to_string(), error.to_string()))?;
+        Ok(Page::new(final_uri, html))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::{cell::RefCell, collections::HashMap};
+
+    use super::*;
+
+    /// Serves pages from memory, and remembers what was asked for.
+    pub(crate) struct MemoryFetcher {
+        pages: HashMap<String, String>,
+        redirects: HashMap<String, String>,
+        pub(crate) fetched: RefCell<Vec<String>>,
+    }
+
+    impl MemoryFetcher {
+        pub(crate) fn new(pages: &[(&str, &str)]) -> Self {
+            Self {
+                pages: pages
+                    .iter()
+                    .map(|(uri, html)| (uri.to_string(), html.to_string()))
+                    .collect(),
+                redirects: HashMap::new(),
+                fetched: RefCell::new(Vec::new()),
+            }
+        }
+
+        pub(crate) fn insert(&mut self, uri: &str, html: &str) {
+            self.pages.insert(uri.to_string(), html.to_string());
+        }
+
+        pub(crate) fn redirect(&mut self, from: &str, to: &str) {
+            self.redirects.insert(from.to_string(), to.to_string());
+        }
+    }
+
+    impl Fetcher for MemoryFetcher {
+        fn fetch(&self, uri: &IriAbsoluteStr) -> Result<Page, FetchError> {
+            self.fetched.borrow_mut().push(uri.to_string());
+            let uri = match self.redirects.get(uri.as_str()) {
+                Some(target) => target
+                    .as_str()
+                    .try_into()
+                    .map_err(|_| FetchError::InvalidUrl(target.clone()))?,
+                None => uri,
+            };
+            let html = self
+                .pages
+                .get(uri.as_str())
+                .ok_or_else(|| FetchError::Status(404, uri.to_string()))?;
+            // fragments are wrapped, whole documents served as they are
+            let html = if html.starts_with("<html") {
+                html.clone()
+            } else {
+                format!("<html><body>{html}</body></html>")
+            };
+            Ok(Page::new(uri.to_owned(), html))
+        }
+    }
+}
@@ -0,0 +1,12 @@
+mod crawler;
+mod fetch;
+mod reindex;
+
+pub use crawler::{CrawlError, CrawlReport, Crawler};
+pub use fetch::{FetchError, Fetcher, HttpFetcher, Page};
+pub use reindex::ReindexReport;
+
+#[cfg(test)]
+pub(crate) mod tests {
+    pub(crate) use super::fetch::tests::MemoryFetcher;
+}
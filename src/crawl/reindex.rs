@@ -0,0 +1,475 @@
+use iri_string::types::{IriAbsoluteStr, IriAbsoluteString};
+
+use crate::{
+    bubble::{Admission, Expansion, Reach},
+    search::Index,
+};
+
+use super::{
+    crawler::{CrawlReport, Crawler},
+    fetch::Fetcher,
+};
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ReindexReport {
+    pub(crate) removed: Vec<IriAbsoluteString>,
+    pub(crate) crawl: CrawlReport,
+}
+
+impl ReindexReport {
+    pub fn removed(&self) -> &[IriAbsoluteString] {
+        &self.removed
+    }
+
+    pub fn crawl(&self) -> &CrawlReport {
+        &self.crawl
+    }
+}
+
+impl Crawler {
+    /// Bring the index up to date after a bubble changed. Documents the new
+    /// expansion no longer admits are purged and only new scopes are
+    /// crawled; everything else is left alone. Both expansions are compared
+    /// whole, so the root's excludes reach expanded bubbles and a bubble no
+    /// longer expanded takes its documents with it. Documents that `others`,
+    /// the expansions of the other bubbles sharing the index, still admit are
+    /// kept, as tombstones would hide them from those bubbles too.
+    pub fn reindex(
+        &self,
+        fetcher: &dyn Fetcher,
+        index: &mut Index,
+        previous: &Expansion,
+        current: &Expansion,
+        others: &[&Expansion],
+    ) -> ReindexReport {
+        let mut report = ReindexReport::default();
+        let diff = current.root().diff(previous.root());
+        if diff.is_empty() {
+            return report;
+        }
+
+        // a bubble that couldn't be expanded this time may well still admit
+        // what it used to
+        let unresolved = |admission: &Admission| {
+            admission.reach().via().iter().any(|reference| {
+                current
+                    .unresolved()
+                    .iter()
+                    .any(|(uri, _)| uri == reference.uri())
+            })
+        };
+        let dropped = index
+            .uris()
+            .filter(|uri| {
+                let admitting = previous.admitting(uri);
+                !admitting.is_empty()
+                    && !admitting.iter().any(unresolved)
+                    && !current.admits(uri)
+                    && !others.iter().any(|other| other.admits(uri))
+            })
+            .map(|uri| uri.to_owned())
+            .collect::<Vec<_>>();
+        for uri in dropped {
+            index.purge(&uri);
+            report.removed.push(uri);
+        }
+        // the curator let these back in
+        let readmitted = index
+            .tombstones()
+            .filter(|uri| current.admits(uri))
+            .map(|uri| uri.to_owned())
+            .collect::<Vec<_>>();
+        for uri in readmitted {
+            index.revive(&uri);
+        }
+
+        let root = Reach::root();
+        let admits = |uri: &IriAbsoluteStr| !current.is_excluded(&root, uri);
+        for indexable in diff.added_indexables() {
+            report.crawl.merge(self.crawl_from(
+                fetcher,
+                index,
+                &admits,
+                indexable,
+                indexable.uri(),
+                false,
+            ));
+        }
+        // content that used to be excluded is crawled from where the exclude was
+        for exclude in diff.removed_excludes() {
+            for indexable in current
+                .root()
+                .indexables()
+                .iter()
+                .filter(|indexable| indexable.admits(exclude.uri()))
+            {
+                report.crawl.merge(self.crawl_from(
+                    fetcher,
+                    index,
+                    &admits,
+                    indexable,
+                    exclude.uri(),
+                    false,
+                ));
+            }
+        }
+        // newly expanded bubbles, with whatever they expand into in turn
+        if !diff.added_expands().is_empty() {
+            report
+                .crawl
+                .merge(self.crawl_expansion(fetcher, index, current));
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        bubble::{Bubble, Limits},
+        crawl::fetch::{tests::MemoryFetcher, Fetcher},
+    };
+
+    use super::*;
+
+    // the bubbles on their own, without expanding
+    fn reindex(
+        crawler: &Crawler,
+        fetcher: &dyn Fetcher,
+        index: &mut Index,
+        previous: &Bubble,
+        current: &Bubble,
+    ) -> ReindexReport {
+        crawler.reindex(
+            fetcher,
+            index,
+            &Expansion::new(previous),
+            &Expansion::new(current),
+            &[],
+        )
+    }
+
+    // expanding into a friend's bubble that includes another.com
+    fn expand(root: &Bubble) -> Expansion<'_> {
+        Expansion::resolve(root, &Limits::default(), |_| {
+            Bubble::parse_html(
+                r#"<html><head><title>Friend</title></head><body>
+                    <a class="bubble-search-page" href="https://another.com">Another</a>
+                </body></html>"#,
+            )
+        })
+    }
+
+    fn bubble(body: &str) -> Bubble {
+        Bubble::parse_html(&format!(
+            "<html><head><title>Test</title></head><body>{body}</body></html>"
+        ))
+        .unwrap()
+    }
+
+    fn fetcher() -> MemoryFetcher {
+        MemoryFetcher::new(&[
+            (
+                "https://example.com",
+                r#"<a href="/a">A</a> <a href="/spam">Spam</a>"#,
+            ),
+            ("https://example.com/a", "A"),
+            (
+                "https://example.com/spam",
+                r#"<a href="/spam/more">More</a>"#,
+            ),
+            ("https://example.com/spam/more", "More spam"),
+            ("https://another.com", "Another"),
+        ])
+    }
+
+    fn indexed(index: &Index) -> Vec<&str> {
+        index.uris().map(|uri| uri.as_str()).collect()
+    }
+
+    #[test]
+    fn test_reindex_added_indexable_only_crawls_new_scope() {
+        let previous =
+            bubble(r#"<a class="bubble-search-site" href="https://example.com">Example</a>"#);
+        let current = bubble(
+            r#"<a class="bubble-search-site" href="https://example.com">Example</a>
+               <a class="bubble-search-page" href="https://another.com">Another</a>"#,
+        );
+        let mut index = Index::new();
+        let crawler = Crawler::new();
+        crawler.crawl_bubble(&fetcher(), &mut index, &previous);
+
+        let fetcher = fetcher();
+        let report = reindex(&crawler, &fetcher, &mut index, &previous, &current);
+        assert_eq!(*fetcher.fetched.borrow(), vec!["https://another.com"]);
+        assert!(report.removed().is_empty());
+        assert_eq!(index.len(), 5);
+    }
+
+    #[test]
+    fn test_reindex_added_exclude_removes_documents() {
+        let previous =
+            bubble(r#"<a class="bubble-search-site" href="https://example.com">Example</a>"#);
+        let current = bubble(
+            r#"<a class="bubble-search-site" href="https://example.com">Example</a>
+               <a class="bubble-exclude-path" href="https://example.com/spam">Spam</a>"#,
+        );
+        let mut index = Index::new();
+        let crawler = Crawler::new();
+        crawler.crawl_bubble(&fetcher(), &mut index, &previous);
+
+        let fetcher = fetcher();
+        let report = reindex(&crawler, &fetcher, &mut index, &previous, &current);
+        assert!(fetcher.fetched.borrow().is_empty());
+        assert_eq!(
+            report
+                .removed()
+                .iter()
+                .map(|uri| uri.as_str())
+                .collect::<Vec<_>>(),
+            vec!["https://example.com/spam", "https://example.com/spam/more"]
+        );
+        assert_eq!(
+            indexed(&index),
+            vec!["https://example.com", "https://example.com/a"]
+        );
+    }
+
+    #[test]
+    fn test_reindex_removed_indexable_keeps_other_bubbles() {
+        let previous = bubble(
+            r#"<a class="bubble-search-page" href="https://example.com/a">A</a>
+               <a class="bubble-search-page" href="https://another.com">Another</a>"#,
+        );
+        let current = bubble(r#"<a class="bubble-search-page" href="https://example.com/a">A</a>"#);
+        let unrelated = bubble(r#"<a class="bubble-search-page" href="https://example.com">E</a>"#);
+        let mut index = Index::new();
+        let crawler = Crawler::new();
+        crawler.crawl_bubble(&fetcher(), &mut index, &previous);
+        crawler.crawl_bubble(&fetcher(), &mut index, &unrelated);
+
+        crawler.reindex(
+            &fetcher(),
+            &mut index,
+            &Expansion::new(&previous),
+            &Expansion::new(&current),
+            &[&Expansion::new(&unrelated)],
+        );
+        assert_eq!(
+            indexed(&index),
+            vec!["https://example.com", "https://example.com/a"]
+        );
+    }
+
+    #[test]
+    fn test_reindex_removed_indexable_tombstones_unshared_documents() {
+        let previous =
+            bubble(r#"<a class="bubble-search-site" href="https://example.com">Example</a>"#);
+        let current =
+            bubble(r#"<a class="bubble-search-page" href="https://another.com">Another</a>"#);
+        // another bubble sharing the index admits one of the dropped pages
+        let shared = bubble(r#"<a class="bubble-search-page" href="https://example.com/a">A</a>"#);
+        let mut index = Index::new();
+        let crawler = Crawler::new();
+        crawler.crawl_bubble(&fetcher(), &mut index, &previous);
+
+        let report = crawler.reindex(
+            &fetcher(),
+            &mut index,
+            &Expansion::new(&previous),
+            &Expansion::new(&current),
+            &[&Expansion::new(&shared)],
+        );
+        assert_eq!(report.removed().len(), 3);
+        assert_eq!(index.tombstones().count(), 3);
+        let kept = vec!["https://another.com", "https://example.com/a"];
+        assert_eq!(indexed(&index), kept);
+
+        // a crawl from the old bubble doesn't resurrect the others
+        crawler.crawl_bubble(&fetcher(), &mut index, &previous);
+        assert_eq!(indexed(&index), kept);
+    }
+
+    #[test]
+    fn test_reindex_added_expand_crawls_expanded_bubble() {
+        let previous =
+            bubble(r#"<a class="bubble-search-page" href="https://example.com/a">A</a>"#);
+        let current = bubble(
+            r#"<a class="bubble-search-page" href="https://example.com/a">A</a>
+               <a class="bubble-expand" href="https://friend.com/bubble">Friend</a>"#,
+        );
+        let mut index = Index::new();
+        let crawler = Crawler::new();
+        crawler.crawl_bubble(&fetcher(), &mut index, &previous);
+
+        let mut fetcher = fetcher();
+        fetcher.insert(
+            "https://friend.com/bubble",
+            r#"<html><head><title>Friend</title></head><body>
+                <a class="bubble-search-page" href="https://another.com">Another</a>
+            </body></html>"#,
+        );
+        let (previous, current) = (
+            Expansion::new(&previous),
+            crawler.expand(&fetcher, &current),
+        );
+        crawler.reindex(&fetcher, &mut index, &previous, &current, &[]);
+        assert_eq!(
+            *fetcher.fetched.borrow(),
+            vec!["https://friend.com/bubble", "https://another.com"]
+        );
+        assert_eq!(
+            indexed(&index),
+            vec!["https://another.com", "https://example.com/a"]
+        );
+    }
+
+    #[test]
+    fn test_reindex_added_exclude_applies_to_expanded_bubbles() {
+        let previous = bubble(
+            r#"<a class="bubble-search-page" href="https://example.com/a">A</a>
+               <a class="bubble-expand" href="https://friend.com/bubble">Friend</a>"#,
+        );
+        let current = bubble(
+            r#"<a class="bubble-search-page" href="https://example.com/a">A</a>
+               <a class="bubble-expand" href="https://friend.com/bubble">Friend</a>
+               <a class="bubble-exclude-path" href="https://another.com">Another</a>"#,
+        );
+        let (previous, current) = (expand(&previous), expand(&current));
+        let mut index = Index::new();
+        let crawler = Crawler::new();
+        crawler.crawl_expansion(&fetcher(), &mut index, &previous);
+        assert_eq!(index.len(), 2);
+
+        let report = crawler.reindex(&fetcher(), &mut index, &previous, &current, &[]);
+        assert_eq!(report.removed()[0].as_str(), "https://another.com");
+        assert_eq!(indexed(&index), vec!["https://example.com/a"]);
+    }
+
+    #[test]
+    fn test_reindex_removed_expand_drops_its_documents() {
+        let previous = bubble(
+            r#"<a class="bubble-search-page" href="https://example.com/a">A</a>
+               <a class="bubble-expand" href="https://friend.com/bubble">Friend</a>"#,
+        );
+        let current = bubble(r#"<a class="bubble-search-page" href="https://example.com/a">A</a>"#);
+        let (previous, current) = (expand(&previous), expand(&current));
+        let mut index = Index::new();
+        let crawler = Crawler::new();
+        crawler.crawl_expansion(&fetcher(), &mut index, &previous);
+
+        let report = crawler.reindex(&fetcher(), &mut index, &previous, &current, &[]);
+        assert_eq!(report.removed()[0].as_str(), "https://another.com");
+        assert_eq!(indexed(&index), vec!["https://example.com/a"]);
+        assert_eq!(index.tombstones().count(), 1);
+    }
+
+    #[test]
+    fn test_reindex_keeps_documents_of_unresolved_bubbles() {
+        let previous = bubble(
+            r#"<a class="bubble-search-page" href="https://example.com/a">A</a>
+               <a class="bubble-expand" href="https://friend.com/bubble">Friend</a>"#,
+        );
+        let current = bubble(
+            r#"<a class="bubble-search-page" href="https://example.com/a">A</a>
+               <a class="bubble-search-page" href="https://example.com">Example</a>
+               <a class="bubble-expand" href="https://friend.com/bubble">Friend</a>"#,
+        );
+        let previous = expand(&previous);
+        // the friend is down this time
+        let current = Expansion::resolve(&current, &Limits::default(), |_| {
+            Err::<Bubble, _>("unreachable")
+        });
+        let mut index = Index::new();
+        let crawler = Crawler::new();
+        crawler.crawl_expansion(&fetcher(), &mut index, &previous);
+
+        let report = crawler.reindex(&fetcher(), &mut index, &previous, &current, &[]);
+        assert!(report.removed().is_empty());
+        assert!(indexed(&index).contains(&"https://another.com"));
+    }
+
+    #[test]
+    fn test_reindex_removed_exclude_crawls_from_exclude() {
+        let previous = bubble(
+            r#"<a class="bubble-search-site" href="https://example.com">Example</a>
+               <a class="bubble-exclude-path" href="https://example.com/spam">Spam</a>"#,
+        );
+        let current =
+            bubble(r#"<a class="bubble-search-site" href="https://example.com">Example</a>"#);
+        let mut index = Index::new();
+        let crawler = Crawler::new();
+        crawler.crawl_bubble(&fetcher(), &mut index, &previous);
+        assert_eq!(index.len(), 2);
+
+        let fetcher = fetcher();
+        reindex(&crawler, &fetcher, &mut index, &previous, &current);
+        assert_eq!(
+            *fetcher.fetched.borrow(),
+            vec!["https://example.com/spam", "https://example.com/spam/more"]
+        );
+        assert_eq!(index.len(), 4);
+    }
+
+    #[test]
+    fn test_reindex_narrowed_site_to_path() {
+        let previous =
+            bubble(r#"<a class="bubble-search-site" href="https://example.com">Example</a>"#);
+        let current =
+            bubble(r#"<a class="bubble-search-path" href="https://example.com/spam">Spam</a>"#);
+        let mut index = Index::new();
+        let crawler = Crawler::new();
+        crawler.crawl_bubble(&fetcher(), &mut index, &previous);
+
+        let fetcher = fetcher();
+        reindex(&crawler, &fetcher, &mut index, &previous, &current);
+        assert!(fetcher.fetched.borrow().is_empty());
+        assert_eq!(
+            indexed(&index),
+            vec!["https://example.com/spam", "https://example.com/spam/more"]
+        );
+        assert_eq!(index.tombstones().count(), 2);
+    }
+
+    #[test]
+    fn test_reindex_tombstones_purged_documents() {
+        let previous =
+            bubble(r#"<a class="bubble-search-site" href="https://example.com">Example</a>"#);
+        let current = bubble(
+            r#"<a class="bubble-search-site" href="https://example.com">Example</a>
+               <a class="bubble-exclude-path" href="https://example.com/spam">Spam</a>"#,
+        );
+        let mut index = Index::new();
+        let crawler = Crawler::new();
+        crawler.crawl_bubble(&fetcher(), &mut index, &previous);
+        reindex(&crawler, &fetcher(), &mut index, &previous, &current);
+
+        // a crawl that still works from the old bubble doesn't resurrect them
+        crawler.crawl_bubble(&fetcher(), &mut index, &previous);
+        assert_eq!(
+            indexed(&index),
+            vec!["https://example.com", "https://example.com/a"]
+        );
+
+        // until the curator removes the exclude again
+        reindex(&crawler, &fetcher(), &mut index, &current, &previous);
+        assert_eq!(index.len(), 4);
+        assert_eq!(index.tombstones().count(), 0);
+    }
+
+    #[test]
+    fn test_reindex_unchanged_does_nothing() {
+        let html = r#"<a class="bubble-search-site" href="https://example.com">Example</a>"#;
+        let mut index = Index::new();
+        let fetcher = fetcher();
+        let report = reindex(
+            &Crawler::new(),
+            &fetcher,
+            &mut index,
+            &bubble(html),
+            &bubble(html),
+        );
+        assert_eq!(report, ReindexReport::default());
+        assert!(fetcher.fetched.borrow().is_empty());
+    }
+}
@@ -0,0 +1,337 @@
+use iri_string::{
+    format::ToDedicatedString,
+    types::{IriAbsoluteStr, IriAbsoluteString, IriReferenceStr},
+};
+use scraper::{error::SelectorErrorKind, ElementRef, Html, Node, Selector};
+use thiserror::Error;
+
+use super::{model::is_iso_date, Document};
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum DocumentParseError {
+    #[error("Selector error: {0}")]
+    SelectorError(String),
+}
+
+impl<'a> From<SelectorErrorKind<'a>> for DocumentParseError {
+    fn from(error: SelectorErrorKind<'a>) -> Self {
+        DocumentParseError::SelectorError(error.to_string())
+    }
+}
+
+// elements whose text is never shown to a reader
+const SKIPPED_ELEMENTS: [&str; 4] = ["script", "style", "noscript", "template"];
+
+// elements that flow within a line of text, so they don't separate words
+const INLINE_ELEMENTS: [&str; 22] = [
+    "a", "abbr", "b", "bdi", "bdo", "cite", "code", "data", "dfn", "em", "i", "kbd", "mark", "q",
+    "s", "samp", "small", "span", "strong", "sub", "sup", "time",
+];
+
+impl Document {
+    pub fn parse_html(uri: IriAbsoluteString, document: &str) -> Result<Self, DocumentParseError> {
+        let document = Html::parse_document(document);
+        let title = Self::parse_title(&document)?;
+        let headings = Self::parse_headings(&document)?;
+        let body = Self::parse_body(&document)?;
+        let lang = Self::parse_lang(&document)?;
+        let date = Self::parse_date(&document)?;
+        let links = Self::parse_links(&document, &uri)?;
+        let canonical = Self::parse_canonical(&document, &uri)?;
+        Ok(Document {
+            uri,
+            title,
+            headings,
+            body,
+            lang,
+            date,
+            links,
+            canonical,
+        })
+    }
+
+    fn parse_canonical(
+        html: &Html,
+        base: &IriAbsoluteStr,
+    ) -> Result<Option<IriAbsoluteString>, DocumentParseError> {
+        let selector = Selector::parse("link[rel~='canonical'][href]")?;
+        Ok(html
+            .select(&selector)
+            .filter_map(|link| link.value().attr("href"))
+            .find_map(|href| resolve(href, base)))
+    }
+
+    // links resolved against the page, deduplicated in document order
+    fn parse_links(
+        html: &Html,
+        base: &IriAbsoluteStr,
+    ) -> Result<Vec<IriAbsoluteString>, DocumentParseError> {
+        let selector = Selector::parse("a[href]")?;
+        let mut links: Vec<IriAbsoluteString> = Vec::new();
+        for link in html
+            .select(&selector)
+            .filter_map(|anchor| anchor.value().attr("href"))
+            .filter_map(|href| resolve(href, base))
+        {
+            if link != *base && !links.contains(&link) {
+                links.push(link);
+            }
+        }
+        Ok(links)
+    }
+
+    fn parse_lang(html: &Html) -> Result<Option<String>, DocumentParseError> {
+        let selector = Selector::parse("html[lang]")?;
+        Ok(html
+            .select(&selector)
+            .next()
+            .and_then(|html| html.value().attr("lang"))
+            .map(|lang| lang.trim().to_string())
+            .filter(|lang| !lang.is_empty()))
+    }
+
+    // the publication date, from the metadata we know or the first time element
+    fn parse_date(html: &Html) -> Result<Option<String>, DocumentParseError> {
+        let candidates = [
+            ("meta[property='article:published_time']", "content"),
+            ("meta[name='date']", "content"),
+            ("meta[name='dcterms.date']", "content"),
+            ("time[datetime]", "datetime"),
+        ];
+        for (selector, attribute) in candidates {
+            let selector = Selector::parse(selector)?;
+            let date = html
+                .select(&selector)
+                .filter_map(|element| element.value().attr(attribute))
+                .find_map(parse_iso_date);
+            if date.is_some() {
+                return Ok(date);
+            }
+        }
+        Ok(None)
+    }
+
+    fn parse_title(html: &Html) -> Result<String, DocumentParseError> {
+        let selector = Selector::parse("head title")?;
+        Ok(html
+            .select(&selector)
+            .next()
+            .map(|title| normalize_whitespace(&title.text().collect::<String>()))
+            .unwrap_or_default())
+    }
+
+    fn parse_headings(html: &Html) -> Result<Vec<String>, DocumentParseError> {
+        let selector = Selector::parse("h1, h2, h3, h4, h5, h6")?;
+        Ok(html
+            .select(&selector)
+            .map(|heading| normalize_whitespace(&visible_text(heading)))
+            .filter(|heading| !heading.is_empty())
+            .collect())
+    }
+
+    fn parse_body(html: &Html) -> Result<String, DocumentParseError> {
+        let selector = Selector::parse("body")?;
+        Ok(html
+            .select(&selector)
+            .next()
+            .map(|body| normalize_whitespace(&visible_text(body)))
+            .unwrap_or_default())
+    }
+}
+
+// resolve a possibly relative reference, dropping any fragment
+fn resolve(href: &str, base: &IriAbsoluteStr) -> Option<IriAbsoluteString> {
+    let reference = IriReferenceStr::new(href.trim()).ok()?;
+    let resolved = reference
+        .resolve_against(base)
+        .try_to_dedicated_string()
+        .ok()?;
+    Some(resolved.into_absolute())
+}
+
+fn visible_text(element: ElementRef) -> String {
+    let mut text = String::new();
+    collect_text(element, &mut text);
+    text
+}
+
+fn collect_text(element: ElementRef, text: &mut String) {
+    for child in element.children() {
+        match child.value() {
+            Node::Text(t) => text.push_str(t),
+            Node::Element(e) if !SKIPPED_ELEMENTS.contains(&e.name()) => {
+                // block boundaries shouldn't glue words together
+                let block = !INLINE_ELEMENTS.contains(&e.name());
+                if block {
+                    text.push(' ');
+                }
+                if let Some(child) = ElementRef::wrap(child) {
+                    collect_text(child, text);
+                }
+                if block {
+                    text.push(' ');
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+// keep the date part of an ISO 8601 timestamp: 2024, 2024-05 or 2024-05-17
+pub(crate) fn parse_iso_date(text: &str) -> Option<String> {
+    let text = text.trim();
+    let date = text.split(['T', ' ']).next()?;
+    is_iso_date(date).then(|| date.to_string())
+}
+
+// The language of a link's text: its own lang, the hreflang of what it
+// links to, or the lang of the nearest element that has one.
+pub(crate) fn element_lang(element: ElementRef) -> Option<String> {
+    lang_attribute(element, "lang")
+        .or_else(|| lang_attribute(element, "hreflang"))
+        .or_else(|| {
+            element
+                .ancestors()
+                .filter_map(ElementRef::wrap)
+                .find_map(|ancestor| lang_attribute(ancestor, "lang"))
+        })
+        .map(str::to_string)
+}
+
+fn lang_attribute<'a>(element: ElementRef<'a>, name: &str) -> Option<&'a str> {
+    element
+        .value()
+        .attr(name)
+        .map(str::trim)
+        .filter(|lang| !lang.is_empty())
+}
+
+fn normalize_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_document() {
+        let html = r#"<html>
+            <head>
+                <title>  HTML   forms </title>
+                <style>body { color: red; }</style>
+            </head>
+            <body>
+                <h1>Forms</h1>
+                <p>A form collects <em>input</em>.</p>
+                <script>var ignored = 1;</script>
+                <h2>Buttons</h2>
+                <p>Press<br>here</p>
+            </body>
+        </html>"#;
+        let document =
+            Document::parse_html("https://example.com/forms".parse().unwrap(), html).unwrap();
+        assert_eq!(document.title, "HTML forms");
+        assert_eq!(document.headings, vec!["Forms", "Buttons"]);
+        assert_eq!(
+            document.body,
+            "Forms A form collects input. Buttons Press here"
+        );
+    }
+
+    #[test]
+    fn test_parse_document_without_title() {
+        let html = r#"<html><body><p>Text</p></body></html>"#;
+        let document = Document::parse_html("https://example.com".parse().unwrap(), html).unwrap();
+        assert_eq!(document.title, "");
+        assert_eq!(document.body, "Text");
+        assert_eq!(document.lang, None);
+        assert_eq!(document.date, None);
+    }
+
+    #[test]
+    fn test_parse_links() {
+        let html = r#"<html><body>
+            <a href="/b">B</a>
+            <a href="c#section">C</a>
+            <a href="https://another.com/d">D</a>
+            <a href="/b#again">B again</a>
+            <a href="https://example.com/a/">Self</a>
+            <a href="http://[broken">Broken</a>
+        </body></html>"#;
+        let document =
+            Document::parse_html("https://example.com/a/".parse().unwrap(), html).unwrap();
+        assert_eq!(
+            document
+                .links
+                .iter()
+                .map(|link| link.as_str())
+                .collect::<Vec<_>>(),
+            vec![
+                "https://example.com/b",
+                "https://example.com/a/c",
+                "https://another.com/d"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_canonical() {
+        let html = r#"<html>
+            <head><link rel="canonical" href="/article"></head>
+            <body>Print view</body>
+        </html>"#;
+        let document =
+            Document::parse_html("https://example.com/article/print".parse().unwrap(), html)
+                .unwrap();
+        assert_eq!(
+            document.canonical.as_ref().map(|uri| uri.as_str()),
+            Some("https://example.com/article")
+        );
+    }
+
+    #[test]
+    fn test_parse_lang_and_date() {
+        let html = r#"<html lang="nl-BE">
+            <head>
+                <meta property="article:published_time" content="2023-04-05T10:00:00Z">
+            </head>
+            <body><time datetime="2020-01-01">Long ago</time></body>
+        </html>"#;
+        let document = Document::parse_html("https://example.com".parse().unwrap(), html).unwrap();
+        assert_eq!(document.lang.as_deref(), Some("nl-BE"));
+        assert_eq!(document.date.as_deref(), Some("2023-04-05"));
+    }
+
+    #[test]
+    fn test_element_lang() {
+        let html = Html::parse_document(
+            r#"<html lang="en"><body><div lang="nl">
+                <a id="own" lang="fr" hreflang="de">Bonjour</a>
+                <a id="hreflang" hreflang="de">Hallo</a>
+                <a id="inherited">Hallo</a>
+            </div><a id="page">Hello</a></body></html>"#,
+        );
+        let lang = |id: &str| {
+            let selector = Selector::parse(&format!("#{id}")).unwrap();
+            element_lang(html.select(&selector).next().unwrap())
+        };
+        assert_eq!(lang("own").as_deref(), Some("fr"));
+        assert_eq!(lang("hreflang").as_deref(), Some("de"));
+        assert_eq!(lang("inherited").as_deref(), Some("nl"));
+        assert_eq!(lang("page").as_deref(), Some("en"));
+        let html = Html::parse_document("<html><body><a>Hello</a></body></html>");
+        let anchor = html.select(&Selector::parse("a").unwrap()).next().unwrap();
+        assert_eq!(element_lang(anchor), None);
+    }
+
+    #[test]
+    fn test_parse_date_from_time_element() {
+        let html = r#"<html><body>
+            <time datetime="yesterday">Yesterday</time>
+            <time datetime="2021-07">July</time>
+        </body></html>"#;
+        let document = Document::parse_html("https://example.com".parse().unwrap(), html).unwrap();
+        assert_eq!(document.date.as_deref(), Some("2021-07"));
+    }
+}
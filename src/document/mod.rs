@@ -0,0 +1,7 @@
+mod html;
+mod model;
+
+pub use html::DocumentParseError;
+pub(crate) use html::{element_lang, parse_iso_date};
+pub(crate) use model::is_iso_date;
+pub use model::Document;
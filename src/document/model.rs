@@ -0,0 +1,96 @@
+use iri_string::types::{IriAbsoluteStr, IriAbsoluteString};
+use serde::{Deserialize, Serialize};
+
+/// The text extracted from a crawled page, split into the fields we index.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Document {
+    pub(super) uri: IriAbsoluteString,
+    pub(super) title: String,
+    pub(super) headings: Vec<String>,
+    pub(super) body: String,
+    pub(super) lang: Option<String>,
+    // ISO 8601 date (or a prefix of one, like 2024-05) the page was published
+    pub(super) date: Option<String>,
+    // outgoing links, resolved and without fragments
+    pub(super) links: Vec<IriAbsoluteString>,
+    // where the page says its original lives, from <link rel="canonical">
+    pub(super) canonical: Option<IriAbsoluteString>,
+}
+
+impl Document {
+    pub fn new(uri: IriAbsoluteString, title: String, headings: Vec<String>, body: String) -> Self {
+        Self {
+            uri,
+            title,
+            headings,
+            body,
+            lang: None,
+            date: None,
+            links: Vec::new(),
+            canonical: None,
+        }
+    }
+
+    pub fn with_lang(mut self, lang: &str) -> Self {
+        self.lang = Some(lang.to_string());
+        self
+    }
+
+    pub fn with_date(mut self, date: &str) -> Self {
+        self.date = Some(date.to_string());
+        self
+    }
+
+    pub fn with_links(mut self, links: Vec<IriAbsoluteString>) -> Self {
+        self.links = links;
+        self
+    }
+
+    pub fn with_canonical(mut self, canonical: IriAbsoluteString) -> Self {
+        self.canonical = Some(canonical);
+        self
+    }
+
+    pub fn uri(&self) -> &IriAbsoluteStr {
+        &self.uri
+    }
+
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    pub fn headings(&self) -> &[String] {
+        &self.headings
+    }
+
+    pub fn body(&self) -> &str {
+        &self.body
+    }
+
+    pub fn lang(&self) -> Option<&str> {
+        self.lang.as_deref()
+    }
+
+    pub fn date(&self) -> Option<&str> {
+        self.date.as_deref()
+    }
+
+    pub fn links(&self) -> &[IriAbsoluteString] {
+        &self.links
+    }
+
+    pub fn canonical(&self) -> Option<&IriAbsoluteStr> {
+        self.canonical.as_deref()
+    }
+}
+
+/// Is this an ISO 8601 date, possibly without day or month?
+pub(crate) fn is_iso_date(date: &str) -> bool {
+    let parts = date.split('-').collect::<Vec<_>>();
+    let digits =
+        |part: &str, len: usize| part.len() == len && part.bytes().all(|b| b.is_ascii_digit());
+    !parts.is_empty()
+        && parts.len() <= 3
+        && digits(parts[0], 4)
+        && parts[1..].iter().all(|part| digits(part, 2))
+}
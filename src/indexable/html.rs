@@ -1,7 +1,9 @@
-use scraper::{error::SelectorErrorKind, Html, Selector};
+use scraper::{error::SelectorErrorKind, ElementRef, Html, Selector};
 use thiserror::Error;
 
-use super::{Indexable, Scope};
+use crate::document::element_lang;
+
+use super::{Indexable, Refresh, Scope};
 
 #[derive(Error, Debug, PartialEq, Eq)]
 pub enum IndexableParseError {
@@ -9,6 +11,10 @@ pub enum IndexableParseError {
     MissingHref(String),
     #[error("Invalid URL in search link: {0}")]
     InvalidUrl(String),
+    #[error("Invalid data-refresh in search link: {0}")]
+    InvalidRefresh(String),
+    #[error("Invalid data-boost in search link: {0}")]
+    InvalidBoost(String),
     #[error("Selector error: {0}")]
     SelectorError(String),
 }
@@ -53,18 +59,120 @@ impl Indexable {
                     .value()
                     .attr("href")
                     .ok_or_else(|| IndexableParseError::MissingHref(indexable.html()))?;
+                let refresh = indexable
+                    .value()
+                    .attr("data-refresh")
+                    .map(|refresh| {
+                        Refresh::parse(refresh)
+                            .ok_or_else(|| IndexableParseError::InvalidRefresh(indexable.html()))
+                    })
+                    .transpose()?;
+                let boost = match indexable.value().attr("data-boost") {
+                    Some(boost) => boost
+                        .trim()
+                        .parse::<f64>()
+                        .ok()
+                        .filter(|boost| boost.is_finite() && *boost > 0.0)
+                        .ok_or_else(|| IndexableParseError::InvalidBoost(indexable.html()))?,
+                    None => 1.0,
+                };
                 Ok(Indexable {
                     uri: href
                         .try_into()
                         .map_err(|_| IndexableParseError::InvalidUrl(indexable.html()))?,
                     scope: scope.clone(),
                     label: indexable.text().collect(),
+                    lang: element_lang(indexable),
+                    refresh,
+                    boost,
+                    section: section(indexable),
+                    note: note(indexable),
+                    tags: indexable
+                        .value()
+                        .attr("data-tags")
+                        .unwrap_or("")
+                        .split(',')
+                        .map(str::trim)
+                        .filter(|tag| !tag.is_empty())
+                        .map(str::to_string)
+                        .collect(),
                 })
             })
             .collect::<Result<Vec<_>, IndexableParseError>>()
     }
 }
 
+// The text of the nearest heading before the link. Headings inside a
+// section or article only apply within it. h1 is the page title, so
+// sections start at h2.
+fn section(element: ElementRef) -> Option<String> {
+    let mut element = element;
+    loop {
+        for sibling in element.prev_siblings().filter_map(ElementRef::wrap) {
+            if is_heading(sibling) {
+                return Some(heading_text(sibling));
+            }
+            if !is_sectioning(sibling) {
+                // the last heading in it that isn't inside a nested section
+                let heading = sibling
+                    .descendants()
+                    .filter_map(ElementRef::wrap)
+                    .filter(|descendant| {
+                        is_heading(*descendant)
+                            && !descendant
+                                .ancestors()
+                                .filter_map(ElementRef::wrap)
+                                .take_while(|ancestor| ancestor.id() != sibling.id())
+                                .any(is_sectioning)
+                    })
+                    .last();
+                if let Some(heading) = heading {
+                    return Some(heading_text(heading));
+                }
+            }
+        }
+        element = element.parent().and_then(ElementRef::wrap)?;
+    }
+}
+
+// `data-note`, else the link's title, else a <small> right after the link
+fn note(element: ElementRef) -> Option<String> {
+    let small = || {
+        element
+            .next_siblings()
+            .find(|sibling| {
+                !sibling
+                    .value()
+                    .as_text()
+                    .is_some_and(|text| text.trim().is_empty())
+            })
+            .and_then(ElementRef::wrap)
+            .filter(|sibling| sibling.value().name() == "small")
+            .map(|small| small.text().collect::<String>())
+    };
+    let note = element
+        .value()
+        .attr("data-note")
+        .or_else(|| element.value().attr("title"))
+        .map(str::to_string)
+        .or_else(small)?;
+    let note = note.split_whitespace().collect::<Vec<_>>().join(" ");
+    (!note.is_empty()).then_some(note)
+}
+
+fn is_heading(element: ElementRef) -> bool {
+    matches!(element.value().name(), "h2" | "h3" | "h4" | "h5" | "h6")
+}
+
+fn is_sectioning(element: ElementRef) -> bool {
+    matches!(element.value().name(), "section" | "article")
+}
+
+fn heading_text(heading: ElementRef) -> String {
+    let text = heading.text().collect::<String>();
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -82,14 +190,82 @@ mod tests {
         let indexables = Indexable::parse_indexables(&document);
         assert_eq!(
             indexables,
-            Ok(vec![Indexable {
-                uri: "https://example.com".parse().unwrap(),
-                scope: Scope::Page,
-                label: "Example".to_string(),
-            }])
+            Ok(vec![Indexable::new(
+                "https://example.com".parse().unwrap(),
+                Scope::Page,
+                "Example".to_string()
+            )])
         );
     }
 
+    #[test]
+    fn test_parse_sections() {
+        let html = r#"
+        <html>
+            <body>
+                <h1>Rust</h1>
+                <a class="bubble-search-site" href="https://www.rust-lang.org">Home</a>
+                <h2>Official  <em>docs</em></h2>
+                <ul>
+                    <li><a class="bubble-search-site" href="https://doc.rust-lang.org">Docs</a></li>
+                </ul>
+                <section>
+                    <h2>Forums</h2>
+                    <a class="bubble-search-site" href="https://users.rust-lang.org">Users</a>
+                    <a class="bubble-exclude-path" href="https://users.rust-lang.org/spam">Spam</a>
+                </section>
+                <a class="bubble-search-site" href="https://docs.rs">docs.rs</a>
+            </body>
+        </html>
+        "#;
+        let document = Html::parse_document(html);
+        let sections = |indexables: Vec<Indexable>| {
+            indexables
+                .iter()
+                .map(|indexable| indexable.section().map(str::to_string))
+                .collect::<Vec<_>>()
+        };
+        let docs = Some("Official docs".to_string());
+        let forums = Some("Forums".to_string());
+        assert_eq!(
+            sections(Indexable::parse_indexables(&document).unwrap()),
+            vec![None, docs.clone(), forums.clone(), docs]
+        );
+        assert_eq!(
+            sections(Indexable::parse_excludes(&document).unwrap()),
+            vec![forums]
+        );
+    }
+
+    #[test]
+    fn test_parse_notes_and_tags() {
+        let html = r#"
+        <html>
+            <body>
+                <a class="bubble-search-site" href="https://a.example" data-note="Great for beginners" title="A">A</a>
+                <a class="bubble-search-site" href="https://b.example" title="Paywalled after 2023">B</a>
+                <a class="bubble-search-site" href="https://c.example" data-tags="reference, Official,">C</a>
+                <small>The   spec</small>
+                <a class="bubble-search-site" href="https://d.example">D</a> <em>Not a note</em>
+            </body>
+        </html>
+        "#;
+        let document = Html::parse_document(html);
+        let indexables = Indexable::parse_indexables(&document).unwrap();
+        assert_eq!(
+            indexables.iter().map(Indexable::note).collect::<Vec<_>>(),
+            vec![
+                Some("Great for beginners"),
+                Some("Paywalled after 2023"),
+                Some("The spec"),
+                None
+            ]
+        );
+        assert_eq!(indexables[2].tags(), &["reference", "Official"]);
+        assert!(indexables[2].has_tag("official"));
+        assert!(indexables[0].tags().is_empty());
+    }
+
     #[test]
     fn test_parse_indexable_mixture() {
         let html = r#"
@@ -106,25 +282,92 @@ mod tests {
         assert_eq!(
             indexables,
             Ok(vec![
-                Indexable {
-                    uri: "https://example.com/a".parse().unwrap(),
-                    scope: Scope::Page,
-                    label: "Page".to_string(),
-                },
-                Indexable {
-                    uri: "https://example.com".parse().unwrap(),
-                    scope: Scope::Site,
-                    label: "Site".to_string(),
-                },
-                Indexable {
-                    uri: "https://example.com/b".parse().unwrap(),
-                    scope: Scope::Path,
-                    label: "Path".to_string(),
-                }
+                Indexable::new(
+                    "https://example.com/a".parse().unwrap(),
+                    Scope::Page,
+                    "Page".to_string()
+                ),
+                Indexable::new(
+                    "https://example.com".parse().unwrap(),
+                    Scope::Site,
+                    "Site".to_string()
+                ),
+                Indexable::new(
+                    "https://example.com/b".parse().unwrap(),
+                    Scope::Path,
+                    "Path".to_string()
+                )
             ])
         );
     }
 
+    #[test]
+    fn test_parse_indexable_refresh() {
+        let html = r#"
+        <html>
+            <body>
+                <a class="bubble-search-site" href="https://news.example.com" data-refresh="daily">News</a>
+            </body>
+        </html>
+        "#;
+        let document = Html::parse_document(html);
+        let indexables = Indexable::parse_indexables(&document).unwrap();
+        assert_eq!(indexables[0].refresh, Some(Refresh::Daily));
+    }
+
+    #[test]
+    fn test_parse_indexable_invalid_refresh() {
+        let html = r#"
+        <html>
+            <body>
+                <a class="bubble-search-site" href="https://example.com" data-refresh="sometimes">Example</a>
+            </body>
+        </html>
+        "#;
+        let document = Html::parse_document(html);
+        let err = Indexable::parse_indexables(&document).unwrap_err();
+        assert_eq!(
+            err,
+            IndexableParseError::InvalidRefresh(
+                r#"<a class="bubble-search-site" href="https://example.com" data-refresh="sometimes">Example</a>"#
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn test_parse_indexable_boost() {
+        let html = r#"
+        <html>
+            <body>
+                <a class="bubble-search-site" href="https://docs.example.com" data-boost="2.0">Docs</a>
+                <a class="bubble-search-site" href="https://blog.example.com" data-boost=" 0.5 ">Blog</a>
+                <a class="bubble-search-site" href="https://example.com">Example</a>
+            </body>
+        </html>
+        "#;
+        let document = Html::parse_document(html);
+        let indexables = Indexable::parse_indexables(&document).unwrap();
+        assert_eq!(
+            indexables.iter().map(Indexable::boost).collect::<Vec<_>>(),
+            vec![2.0, 0.5, 1.0]
+        );
+    }
+
+    #[test]
+    fn test_parse_indexable_invalid_boost() {
+        for boost in ["lots", "0", "-1", "inf"] {
+            let html = format!(
+                r#"<a class="bubble-search-site" href="https://example.com" data-boost="{boost}">Example</a>"#
+            );
+            let document = Html::parse_document(&html);
+            assert!(matches!(
+                Indexable::parse_indexables(&document),
+                Err(IndexableParseError::InvalidBoost(_))
+            ));
+        }
+    }
+
     #[test]
     fn test_parse_exclude_mixture() {
         let html = r#"
@@ -141,21 +384,21 @@ mod tests {
         assert_eq!(
             indexables,
             Ok(vec![
-                Indexable {
-                    uri: "https://example.com/a".parse().unwrap(),
-                    scope: Scope::Page,
-                    label: "Page".to_string(),
-                },
-                Indexable {
-                    uri: "https://example.com".parse().unwrap(),
-                    scope: Scope::Site,
-                    label: "Site".to_string(),
-                },
-                Indexable {
-                    uri: "https://example.com/b".parse().unwrap(),
-                    scope: Scope::Path,
-                    label: "Path".to_string(),
-                }
+                Indexable::new(
+                    "https://example.com/a".parse().unwrap(),
+                    Scope::Page,
+                    "Page".to_string()
+                ),
+                Indexable::new(
+                    "https://example.com".parse().unwrap(),
+                    Scope::Site,
+                    "Site".to_string()
+                ),
+                Indexable::new(
+                    "https://example.com/b".parse().unwrap(),
+                    Scope::Path,
+                    "Path".to_string()
+                )
             ])
         );
     }
@@ -2,4 +2,4 @@ mod html;
 mod model;
 
 pub use html::IndexableParseError;
-pub use model::{Indexable, Scope};
+pub use model::{Indexable, Refresh, Scope};
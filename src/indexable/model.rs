@@ -1,10 +1,21 @@
-use iri_string::types::IriAbsoluteString;
+use iri_string::types::{IriAbsoluteStr, IriAbsoluteString};
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq)]
 pub struct Indexable {
     pub(super) uri: IriAbsoluteString,
     pub(super) scope: Scope,
     pub(super) label: String,
+    // the language of the label, from `lang` or `hreflang`
+    pub(super) lang: Option<String>,
+    pub(super) refresh: Option<Refresh>,
+    // multiplies the score of documents this admits, from `data-boost`
+    pub(super) boost: f64,
+    // the heading the link is grouped under on the bubble page
+    pub(super) section: Option<String>,
+    // what the curator says about it, like "great for beginners"
+    pub(super) note: Option<String>,
+    // from `data-tags`, comma separated
+    pub(super) tags: Vec<String>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -17,8 +28,214 @@ pub enum Scope {
           // be described
 }
 
+impl Scope {
+    /// The name used in bubble classes like `bubble-search-site`, and as a
+    /// stable name anywhere else a scope is written down.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Scope::Site => "site",
+            Scope::Path => "path",
+            Scope::Page => "page",
+        }
+    }
+}
+
+/// How often the curator expects the content to change, from `data-refresh`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Refresh {
+    Hourly,
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+impl Refresh {
+    pub fn seconds(self) -> u64 {
+        const HOUR: u64 = 60 * 60;
+        match self {
+            Refresh::Hourly => HOUR,
+            Refresh::Daily => 24 * HOUR,
+            Refresh::Weekly => 7 * 24 * HOUR,
+            Refresh::Monthly => 30 * 24 * HOUR,
+            Refresh::Yearly => 365 * 24 * HOUR,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Refresh::Hourly => "hourly",
+            Refresh::Daily => "daily",
+            Refresh::Weekly => "weekly",
+            Refresh::Monthly => "monthly",
+            Refresh::Yearly => "yearly",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "hourly" => Some(Refresh::Hourly),
+            "daily" => Some(Refresh::Daily),
+            "weekly" => Some(Refresh::Weekly),
+            "monthly" => Some(Refresh::Monthly),
+            "yearly" => Some(Refresh::Yearly),
+            _ => None,
+        }
+    }
+}
+
 impl Indexable {
     pub fn new(uri: IriAbsoluteString, scope: Scope, label: String) -> Self {
-        Self { uri, scope, label }
+        Self {
+            uri,
+            scope,
+            label,
+            lang: None,
+            refresh: None,
+            boost: 1.0,
+            section: None,
+            note: None,
+            tags: Vec::new(),
+        }
+    }
+
+    pub fn with_lang(mut self, lang: &str) -> Self {
+        self.lang = Some(lang.to_string());
+        self
+    }
+
+    pub fn with_refresh(mut self, refresh: Refresh) -> Self {
+        self.refresh = Some(refresh);
+        self
+    }
+
+    pub fn with_boost(mut self, boost: f64) -> Self {
+        self.boost = boost;
+        self
+    }
+
+    pub fn with_section(mut self, section: &str) -> Self {
+        self.section = Some(section.to_string());
+        self
+    }
+
+    pub fn with_note(mut self, note: &str) -> Self {
+        self.note = Some(note.to_string());
+        self
+    }
+
+    pub fn with_tag(mut self, tag: &str) -> Self {
+        self.tags.push(tag.to_string());
+        self
+    }
+
+    pub fn uri(&self) -> &IriAbsoluteStr {
+        &self.uri
+    }
+
+    pub fn scope(&self) -> &Scope {
+        &self.scope
+    }
+
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    pub fn lang(&self) -> Option<&str> {
+        self.lang.as_deref()
+    }
+
+    pub fn refresh(&self) -> Option<Refresh> {
+        self.refresh
+    }
+
+    pub fn boost(&self) -> f64 {
+        self.boost
+    }
+
+    pub fn section(&self) -> Option<&str> {
+        self.section.as_deref()
+    }
+
+    pub fn note(&self) -> Option<&str> {
+        self.note.as_deref()
+    }
+
+    pub fn tags(&self) -> &[String] {
+        &self.tags
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|own| own.eq_ignore_ascii_case(tag))
+    }
+
+    /// Does this indexable cover the given URI?
+    pub fn admits(&self, uri: &IriAbsoluteStr) -> bool {
+        match self.scope {
+            Scope::Page => self.uri.as_str() == uri.as_str(),
+            Scope::Site => same_site(&self.uri, uri),
+            Scope::Path => {
+                if !same_site(&self.uri, uri) {
+                    return false;
+                }
+                let prefix = self.uri.path_str();
+                let path = uri.path_str();
+                // a path only admits whole segments, so /b admits /b/c but not /bc
+                prefix.is_empty()
+                    || path == prefix
+                    || (path.starts_with(prefix)
+                        && (prefix.ends_with('/') || path[prefix.len()..].starts_with('/')))
+            }
+        }
+    }
+}
+
+fn same_site(a: &IriAbsoluteStr, b: &IriAbsoluteStr) -> bool {
+    a.scheme_str().eq_ignore_ascii_case(b.scheme_str())
+        && a.authority_str().map(str::to_ascii_lowercase)
+            == b.authority_str().map(str::to_ascii_lowercase)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn indexable(uri: &str, scope: Scope) -> Indexable {
+        Indexable::new(uri.parse().unwrap(), scope, "Label".to_string())
+    }
+
+    fn admits(indexable: &Indexable, uri: &str) -> bool {
+        let uri: IriAbsoluteString = uri.parse().unwrap();
+        indexable.admits(&uri)
+    }
+
+    #[test]
+    fn test_admits_page() {
+        let page = indexable("https://example.com/a", Scope::Page);
+        assert!(admits(&page, "https://example.com/a"));
+        assert!(!admits(&page, "https://example.com/a/b"));
+        assert!(!admits(&page, "https://example.com/"));
+    }
+
+    #[test]
+    fn test_admits_site() {
+        let site = indexable("https://example.com", Scope::Site);
+        assert!(admits(&site, "https://example.com/a"));
+        assert!(admits(&site, "https://EXAMPLE.com/a/b"));
+        assert!(!admits(&site, "https://another.com/a"));
+        assert!(!admits(&site, "https://sub.example.com/a"));
+    }
+
+    #[test]
+    fn test_admits_path() {
+        let path = indexable("https://example.com/b", Scope::Path);
+        assert!(admits(&path, "https://example.com/b"));
+        assert!(admits(&path, "https://example.com/b/c"));
+        assert!(!admits(&path, "https://example.com/bc"));
+        assert!(!admits(&path, "https://example.com/a"));
+        assert!(!admits(&path, "https://another.com/b/c"));
+
+        let trailing = indexable("https://example.com/b/", Scope::Path);
+        assert!(admits(&trailing, "https://example.com/b/c"));
     }
 }
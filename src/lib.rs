@@ -0,0 +1,7 @@
+pub mod bubble;
+pub mod crawl;
+pub mod document;
+pub mod indexable;
+pub mod schedule;
+pub mod search;
+pub mod topic;
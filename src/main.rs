@@ -1,7 +1,461 @@
-mod bubble;
-mod indexable;
-mod topic;
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
-fn main() {
-    println!("Hello, world!");
+use anyhow::{bail, Context, Result};
+use happy_search_bubbles::{
+    bubble::{Bubble, BubbleCache, BubbleReference, Catalogue, Expansion, Reach},
+    crawl::{CrawlReport, Crawler, HttpFetcher},
+    document::Document,
+    schedule::Scheduler,
+    search::Index,
+    topic::{TopicRelation, Wikidata},
+};
+use iri_string::types::IriAbsoluteString;
+
+const USAGE: &str = "usage:
+    happy-search-bubbles add <index> <url> <page.html>
+    happy-search-bubbles crawl <index> <bubble.html>
+    happy-search-bubbles expand <index> <bubble.html>
+    happy-search-bubbles reindex <index> <previous-bubble.html> <bubble.html> [other-bubble.html...]
+    happy-search-bubbles gc <index> <bubble.html>...
+    happy-search-bubbles schedule <index> <bubble.html> <schedule> [other-bubble.html...]
+    happy-search-bubbles upcoming <bubble.html> <schedule>
+    happy-search-bubbles sources <bubble.html>
+    happy-search-bubbles upgrade <bubble.html>
+    happy-search-bubbles topics <bubble.html> <wikidata.json>
+    happy-search-bubbles related <bubble.html> <wikidata.json> <other-bubble.html>...
+    happy-search-bubbles search <index> <bubble.html> <query> [wikidata.json]";
+
+fn main() -> Result<()> {
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+        ["add", index_path, uri, page] => add(Path::new(index_path), uri, Path::new(page)),
+        ["crawl", index_path, bubble] => crawl(Path::new(index_path), Path::new(bubble)),
+        ["expand", index_path, bubble] => refresh(Path::new(index_path), Path::new(bubble)),
+        ["reindex", index_path, previous, bubble, ref others @ ..] => reindex(
+            Path::new(index_path),
+            Path::new(previous),
+            Path::new(bubble),
+            others,
+        ),
+        ["gc", index_path, ref bubbles @ ..] if !bubbles.is_empty() => {
+            gc(Path::new(index_path), bubbles)
+        }
+        ["schedule", index_path, bubble, schedule_path, ref others @ ..] => schedule(
+            Path::new(index_path),
+            Path::new(bubble),
+            Path::new(schedule_path),
+            others,
+        ),
+        ["upcoming", bubble, schedule_path] => {
+            upcoming(Path::new(bubble), Path::new(schedule_path))
+        }
+        ["sources", bubble] => sources(Path::new(bubble)),
+        ["upgrade", bubble] => upgrade(Path::new(bubble)),
+        ["topics", bubble, wikidata] => topics(Path::new(bubble), Path::new(wikidata)),
+        ["related", bubble, wikidata, ref others @ ..] if !others.is_empty() => related(
+            Path::new(bubble),
+            Path::new(wikidata),
+            &others.iter().map(PathBuf::from).collect::<Vec<_>>(),
+        ),
+        ["search", index_path, bubble, query] => {
+            search(Path::new(index_path), Path::new(bubble), query, None)
+        }
+        ["search", index_path, bubble, query, wikidata] => search(
+            Path::new(index_path),
+            Path::new(bubble),
+            query,
+            Some(Path::new(wikidata)),
+        ),
+        _ => bail!(USAGE),
+    }
+}
+
+fn add(index_path: &Path, uri: &str, page: &Path) -> Result<()> {
+    let mut index = Index::load(index_path)?;
+    let html = fs::read_to_string(page).with_context(|| format!("reading {}", page.display()))?;
+    let uri = uri
+        .try_into()
+        .map_err(|_| anyhow::anyhow!("not an absolute URL: {uri}"))?;
+    index.add(Document::parse_html(uri, &html)?);
+    index.save(index_path)?;
+    Ok(())
+}
+
+fn crawl(index_path: &Path, bubble: &Path) -> Result<()> {
+    let mut index = Index::load(index_path)?;
+    let bubble = load_bubble(bubble)?;
+    let crawler = Crawler::new();
+    let fetcher = HttpFetcher::new();
+    let expansion = expand(&crawler, &fetcher, &bubble, index_path)?;
+    let report = crawler.crawl_expansion(&fetcher, &mut index, &expansion);
+    index.save(index_path)?;
+    print_crawl_report(&report);
+    Ok(())
+}
+
+// fetch the expanded bubbles again, for searches to use
+fn refresh(index_path: &Path, bubble: &Path) -> Result<()> {
+    let bubble = load_bubble(bubble)?;
+    let expansion = expand(&Crawler::new(), &HttpFetcher::new(), &bubble, index_path)?;
+    for expanded in expansion.expanded() {
+        println!("expanded {}", expanded.uri());
+    }
+    Ok(())
+}
+
+// the other bubbles sharing the index keep what they admit
+fn reindex(index_path: &Path, previous: &Path, bubble: &Path, others: &[&str]) -> Result<()> {
+    let mut index = Index::load(index_path)?;
+    let previous = load_bubble(previous)?;
+    let bubble = load_bubble(bubble)?;
+    let (crawler, fetcher) = (Crawler::new(), HttpFetcher::new());
+    // the previous version as last expanded, before fetching the current one
+    let previous = expand_offline(&crawler, &previous, index_path)?;
+    let current = expand(&crawler, &fetcher, &bubble, index_path)?;
+    let others = load_bubbles(others)?;
+    let others = others
+        .iter()
+        .map(|other| expand_offline(&crawler, other, index_path))
+        .collect::<Result<Vec<_>>>()?;
+    let others = others.iter().collect::<Vec<_>>();
+    let report = crawler.reindex(&fetcher, &mut index, &previous, &current, &others);
+    index.save(index_path)?;
+    for uri in report.removed() {
+        println!("removed {uri}");
+    }
+    print_crawl_report(report.crawl());
+    Ok(())
+}
+
+// purge documents that none of the bubbles sharing the index admit
+fn gc(index_path: &Path, bubbles: &[&str]) -> Result<()> {
+    let mut index = Index::load(index_path)?;
+    let bubbles = load_bubbles(bubbles)?;
+    // documents crawled for expanded bubbles are still in use; expanding from
+    // the cache keeps gc working while a friend's site is down
+    let crawler = Crawler::new();
+    let expansions = bubbles
+        .iter()
+        .map(|bubble| expand_offline(&crawler, bubble, index_path))
+        .collect::<Result<Vec<_>>>()?;
+    let removed = index.collect_garbage(&expansions.iter().collect::<Vec<_>>())?;
+    index.save(index_path)?;
+    for uri in removed {
+        println!("removed {uri}");
+    }
+    Ok(())
+}
+
+// keeps recrawling as content becomes due; bubble edits are picked up each round
+fn schedule(
+    index_path: &Path,
+    bubble_path: &Path,
+    schedule_path: &Path,
+    others: &[&str],
+) -> Result<()> {
+    let crawler = Crawler::new();
+    let fetcher = HttpFetcher::new();
+    let others = load_bubbles(others)?;
+    let mut index = Index::load(index_path)?;
+    let mut scheduler = Scheduler::load(schedule_path)?;
+    let mut previous = load_bubble(bubble_path)?;
+    loop {
+        let bubble = load_bubble(bubble_path)?;
+        let previous_expansion = expand_offline(&crawler, &previous, index_path)?;
+        // content of expanded bubbles is kept fresh too
+        let expansion = expand(&crawler, &fetcher, &bubble, index_path)?;
+        let other_expansions = others
+            .iter()
+            .map(|other| expand_offline(&crawler, other, index_path))
+            .collect::<Result<Vec<_>>>()?;
+        let report = crawler.reindex(
+            &fetcher,
+            &mut index,
+            &previous_expansion,
+            &expansion,
+            &other_expansions.iter().collect::<Vec<_>>(),
+        );
+        print_crawl_report(report.crawl());
+        for (_, report) in scheduler.tick(&crawler, &fetcher, &mut index, &expansion, now()) {
+            print_crawl_report(&report);
+        }
+        index.save(index_path)?;
+        scheduler.save(schedule_path)?;
+
+        let wakeup = scheduler.next_wakeup(&expansion, now());
+        previous = bubble;
+        // wake up regularly anyway, so bubble edits don't wait for the next crawl
+        thread::sleep(Duration::from_secs(
+            wakeup.saturating_sub(now()).clamp(1, 60 * 60),
+        ));
+    }
+}
+
+fn upcoming(bubble_path: &Path, schedule_path: &Path) -> Result<()> {
+    let scheduler = Scheduler::load(schedule_path)?;
+    let bubble = load_bubble(bubble_path)?;
+    let now = now();
+    let expansion = Crawler::new().expand(&HttpFetcher::new(), &bubble);
+    print_unresolved(&expansion);
+    for crawl in scheduler.upcoming(&expansion) {
+        let wait = crawl.due().saturating_sub(now);
+        println!(
+            "in {:>6}m {} {}",
+            wait / 60,
+            crawl.scope().as_str(),
+            crawl.uri()
+        );
+    }
+    Ok(())
+}
+
+// every indexable and exclude, and which expanded bubbles brought it in
+fn sources(bubble_path: &Path) -> Result<()> {
+    let bubble = load_bubble(bubble_path)?;
+    let expansion = Crawler::new().expand(&HttpFetcher::new(), &bubble);
+    print_unresolved(&expansion);
+    for (kind, sources) in [
+        ("include", expansion.indexables().collect::<Vec<_>>()),
+        ("exclude", expansion.excludes().collect()),
+    ] {
+        for (indexable, reach) in sources {
+            print!("{kind} {:?} {}", indexable.scope(), indexable.uri());
+            if let Some(section) = indexable.section() {
+                print!(" in {section}");
+            }
+            if !reach.via().is_empty() {
+                print!(" via {}", via(reach.via()));
+            }
+            println!();
+        }
+    }
+    for conflict in expansion.conflicts() {
+        let source = |reach: &Reach| match reach.via() {
+            [] => "this bubble".to_string(),
+            references => via(references),
+        };
+        println!(
+            "conflict: {} excluded by {} covers {} included by {}, {}",
+            conflict.exclude().uri(),
+            source(conflict.exclude_reach()),
+            conflict.indexable().uri(),
+            source(conflict.indexable_reach()),
+            if conflict.applies() {
+                "exclude applies"
+            } else {
+                "exclude ignored"
+            }
+        );
+    }
+    Ok(())
+}
+
+// rewrite a bubble page to the current format version, in place
+fn upgrade(bubble_path: &Path) -> Result<()> {
+    let html = fs::read_to_string(bubble_path)
+        .with_context(|| format!("reading {}", bubble_path.display()))?;
+    let upgraded = Bubble::upgrade_html(&html)?;
+    fs::write(bubble_path, upgraded)
+        .with_context(|| format!("writing {}", bubble_path.display()))?;
+    Ok(())
+}
+
+// list a bubble's topics as items in a local Wikidata dump, failing when a
+// link's text doesn't name its item
+fn topics(bubble_path: &Path, wikidata_path: &Path) -> Result<()> {
+    let bubble = load_bubble(bubble_path)?;
+    let wikidata = Wikidata::load(wikidata_path)
+        .with_context(|| format!("reading {}", wikidata_path.display()))?;
+    for reference in bubble.topic().references() {
+        match wikidata.qid(reference) {
+            Some(qid) => {
+                let label = wikidata
+                    .entity(qid)
+                    .and_then(|entity| entity.label("en"))
+                    .unwrap_or("not in dump");
+                println!("{} {qid} ({label})", reference.uri());
+            }
+            None => println!("{} unknown", reference.uri()),
+        }
+    }
+    let mismatches = wikidata.check_labels(bubble.topic());
+    for mismatch in &mismatches {
+        let label = mismatch.label();
+        match label.lang() {
+            Some(lang) => print!("mismatch: {:?} ({lang})", label.text()),
+            None => print!("mismatch: {:?}", label.text()),
+        }
+        println!(" links to {}", mismatch.qid());
+    }
+    if !mismatches.is_empty() {
+        bail!("{} topic labels don't match Wikidata", mismatches.len());
+    }
+    Ok(())
+}
+
+// offer the bubbles about broader or narrower topics than this one
+fn related(bubble_path: &Path, wikidata_path: &Path, other_paths: &[PathBuf]) -> Result<()> {
+    let bubble = load_bubble(bubble_path)?;
+    let wikidata = Wikidata::load(wikidata_path)
+        .with_context(|| format!("reading {}", wikidata_path.display()))?;
+    let mut catalogue = Catalogue::new(wikidata);
+    for path in other_paths {
+        catalogue.insert(file_uri(path)?, load_bubble(path)?);
+    }
+    for related in catalogue.related_bubbles(bubble.topic()) {
+        let relation = match related.relation() {
+            TopicRelation::Equal => "same topic",
+            TopicRelation::Broader => "broader",
+            TopicRelation::Narrower => "narrower",
+        };
+        println!("{relation}: {} {}", related.uri(), related.bubble().title());
+    }
+    Ok(())
+}
+
+// file: URL of a local bubble, percent-encoding what URLs can't contain
+fn file_uri(path: &Path) -> Result<IriAbsoluteString> {
+    let path = fs::canonicalize(path).with_context(|| format!("reading {}", path.display()))?;
+    let mut uri = "file://".to_string();
+    for byte in path.to_string_lossy().bytes() {
+        if byte.is_ascii_alphanumeric() || b"/-._~".contains(&byte) {
+            uri.push(byte as char);
+        } else {
+            uri.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    Ok(uri.try_into()?)
+}
+
+fn via(references: &[BubbleReference]) -> String {
+    references
+        .iter()
+        .map(|reference| match reference.label().trim() {
+            "" => reference.uri().as_str(),
+            label => label,
+        })
+        .collect::<Vec<_>>()
+        .join(" → ")
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("system clock is after 1970")
+        .as_secs()
+}
+
+fn print_crawl_report(report: &CrawlReport) {
+    for uri in report.fetched() {
+        println!("fetched {uri}");
+    }
+    for (uri, error) in report.failed() {
+        eprintln!("failed {uri}: {error}");
+    }
+}
+
+// pinned bubbles are cached next to the index, so their pinned version
+// stays available when they change
+fn expand<'a>(
+    crawler: &Crawler,
+    fetcher: &HttpFetcher,
+    bubble: &'a Bubble,
+    index_path: &Path,
+) -> Result<Expansion<'a>> {
+    let cache_path = bubble_cache_path(index_path);
+    let mut cache = BubbleCache::load(&cache_path)?;
+    let expansion = crawler.expand_cached(fetcher, bubble, &mut cache);
+    cache.save(&cache_path)?;
+    print_unresolved(&expansion);
+    Ok(expansion)
+}
+
+// the expansion as last fetched, from the cache next to the index
+fn expand_offline<'a>(
+    crawler: &Crawler,
+    bubble: &'a Bubble,
+    index_path: &Path,
+) -> Result<Expansion<'a>> {
+    let cache = BubbleCache::load(&bubble_cache_path(index_path))?;
+    let expansion = crawler.expand_offline(bubble, &cache);
+    print_unresolved(&expansion);
+    Ok(expansion)
+}
+
+fn print_unresolved(expansion: &Expansion) {
+    for (uri, error) in expansion.unresolved() {
+        eprintln!("could not expand {uri}: {error}");
+    }
+    for expanded in expansion.expanded() {
+        print_rejected(expanded.bubble());
+    }
+}
+
+fn print_rejected(bubble: &Bubble) {
+    for (uri, error) in bubble.rejected() {
+        eprintln!("skipped {uri} in {}: {error}", bubble.title());
+    }
+}
+
+fn bubble_cache_path(index_path: &Path) -> PathBuf {
+    let mut path = index_path.as_os_str().to_owned();
+    path.push(".bubbles");
+    PathBuf::from(path)
+}
+
+fn load_bubbles(paths: &[&str]) -> Result<Vec<Bubble>> {
+    paths
+        .iter()
+        .map(|path| load_bubble(Path::new(path)))
+        .collect()
+}
+
+fn load_bubble(path: &Path) -> Result<Bubble> {
+    let html = fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+    let bubble = Bubble::parse_html(&html)?;
+    print_rejected(&bubble);
+    Ok(bubble)
+}
+
+// a Wikidata dump adds the aliases of the bubble's topics to the query
+fn search(
+    index_path: &Path,
+    bubble: &Path,
+    query: &str,
+    wikidata_path: Option<&Path>,
+) -> Result<()> {
+    let mut index = Index::load(index_path)?;
+    if let Some(wikidata_path) = wikidata_path {
+        let wikidata = Wikidata::load(wikidata_path)
+            .with_context(|| format!("reading {}", wikidata_path.display()))?;
+        index = index.with_wikidata(wikidata);
+    }
+    let bubble = load_bubble(bubble)?;
+    // searching needs no network: expanded bubbles are as last crawled or
+    // expanded
+    let expansion = expand_offline(&Crawler::new(), &bubble, index_path)?;
+    for result in index.search_expanded(&expansion, query)? {
+        println!("{:.3} {} {}", result.score(), result.uri(), result.title());
+        if !result.snippet().is_empty() {
+            println!("    {}", result.snippet().to_marked("[", "]"));
+        }
+        for duplicate in result.duplicates() {
+            println!("    also at {duplicate}");
+        }
+        if !result.via().is_empty() {
+            println!("    included via {}", via(result.via()));
+        }
+        for note in result.notes() {
+            println!("    note: {note}");
+        }
+        if !result.tags().is_empty() {
+            println!("    tags: {}", result.tags().join(", "));
+        }
+    }
+    Ok(())
 }
@@ -0,0 +1,3 @@
+mod scheduler;
+
+pub use scheduler::{Policy, ScheduleError, ScheduledCrawl, Scheduler};
@@ -0,0 +1,541 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs,
+    io::ErrorKind,
+    path::Path,
+};
+
+use iri_string::types::{IriAbsoluteStr, IriAbsoluteString};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    bubble::Expansion,
+    crawl::{CrawlReport, Crawler, Fetcher},
+    indexable::{Indexable, Refresh, Scope},
+    search::Index,
+};
+
+// weight of the latest crawl in the observed change rate
+const CHANGE_RATE_SMOOTHING: f64 = 0.3;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum ScheduleError {
+    #[error("Could not access schedule file: {0}")]
+    Io(String),
+    #[error("Could not read schedule file: {0}")]
+    Format(String),
+}
+
+impl From<std::io::Error> for ScheduleError {
+    fn from(error: std::io::Error) -> Self {
+        ScheduleError::Io(error.to_string())
+    }
+}
+
+impl From<serde_json::Error> for ScheduleError {
+    fn from(error: serde_json::Error) -> Self {
+        ScheduleError::Format(error.to_string())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Policy {
+    /// Used for indexables without `data-refresh`.
+    pub default_refresh: Refresh,
+    /// The most pages fetched per budget period, over all indexables.
+    pub budget: usize,
+    pub budget_period: u64,
+}
+
+impl Default for Policy {
+    fn default() -> Self {
+        Self {
+            default_refresh: Refresh::Weekly,
+            budget: 10_000,
+            budget_period: Refresh::Daily.seconds(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+struct History {
+    last_crawl: Option<u64>,
+    // how often recent crawls found changes, between 0 and 1
+    change_rate: Option<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScheduledCrawl {
+    uri: IriAbsoluteString,
+    scope: Scope,
+    due: u64,
+}
+
+impl ScheduledCrawl {
+    pub fn uri(&self) -> &IriAbsoluteStr {
+        &self.uri
+    }
+
+    pub fn scope(&self) -> &Scope {
+        &self.scope
+    }
+
+    /// Unix time at which the crawl is due.
+    pub fn due(&self) -> u64 {
+        self.due
+    }
+}
+
+/// Decides when each indexable of a bubble is crawled again. Times are in
+/// seconds since the Unix epoch, passed in so the schedule can be tested.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Scheduler {
+    histories: BTreeMap<String, History>,
+    budget_start: u64,
+    budget_spent: usize,
+    #[serde(skip)]
+    policy: Policy,
+}
+
+impl Scheduler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_policy(mut self, policy: Policy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Load the crawl history from disk. A missing file is an empty history.
+    pub fn load(path: &Path) -> Result<Self, ScheduleError> {
+        match fs::read(path) {
+            Ok(data) => Ok(serde_json::from_slice(&data)?),
+            Err(error) if error.kind() == ErrorKind::NotFound => Ok(Self::new()),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), ScheduleError> {
+        let temporary = path.with_extension("tmp");
+        fs::write(&temporary, serde_json::to_vec(self)?)?;
+        fs::rename(&temporary, path)?;
+        Ok(())
+    }
+
+    /// The time between crawls: the declared refresh, shortened when crawls
+    /// keep finding changes and lengthened when they don't.
+    pub fn interval(&self, indexable: &Indexable) -> u64 {
+        let declared = indexable
+            .refresh()
+            .unwrap_or(self.policy.default_refresh)
+            .seconds() as f64;
+        let factor = match self.history(indexable).and_then(|h| h.change_rate) {
+            // always changing halves the interval, rarely changing quadruples it
+            Some(rate) => (0.5 / rate.max(0.125)).clamp(0.5, 4.0),
+            None => 1.0,
+        };
+        (declared * factor) as u64
+    }
+
+    /// When the indexable should be crawled next. Never crawled means now.
+    pub fn due(&self, indexable: &Indexable) -> u64 {
+        self.history(indexable)
+            .and_then(|history| history.last_crawl)
+            .map_or(0, |last| last + self.interval(indexable))
+    }
+
+    /// The crawls for the indexables of every bubble in the expansion,
+    /// soonest first.
+    pub fn upcoming(&self, expansion: &Expansion) -> Vec<ScheduledCrawl> {
+        let mut seen = HashSet::new();
+        let mut upcoming = expansion
+            .indexables()
+            .filter(|(indexable, _)| seen.insert(key(indexable)))
+            .map(|(indexable, _)| ScheduledCrawl {
+                uri: indexable.uri().to_owned(),
+                scope: indexable.scope().clone(),
+                due: self.due(indexable),
+            })
+            .collect::<Vec<_>>();
+        upcoming.sort_by(|a, b| a.due.cmp(&b.due).then_with(|| a.uri.cmp(&b.uri)));
+        upcoming
+    }
+
+    pub fn remaining_budget(&self, now: u64) -> usize {
+        if now >= self.budget_start + self.policy.budget_period {
+            self.policy.budget
+        } else {
+            self.policy.budget.saturating_sub(self.budget_spent)
+        }
+    }
+
+    /// When there may be work again: the next due crawl, or the start of the
+    /// next budget period if the budget is used up.
+    pub fn next_wakeup(&self, expansion: &Expansion, now: u64) -> u64 {
+        let next_due = self
+            .upcoming(expansion)
+            .first()
+            .map_or(now + self.policy.budget_period, |crawl| crawl.due);
+        if self.remaining_budget(now) == 0 {
+            next_due.max(self.budget_start + self.policy.budget_period)
+        } else {
+            next_due.max(now)
+        }
+    }
+
+    /// Recrawl the indexables of the expansion that are due, most overdue
+    /// first, until the budget runs out. An indexable the budget cut short stays due, and the
+    /// first crawl of one sets no change rate, as there's nothing to compare.
+    pub fn tick(
+        &mut self,
+        crawler: &Crawler,
+        fetcher: &dyn Fetcher,
+        index: &mut Index,
+        expansion: &Expansion,
+        now: u64,
+    ) -> Vec<(IriAbsoluteString, CrawlReport)> {
+        if now >= self.budget_start + self.policy.budget_period {
+            self.budget_start = now;
+            self.budget_spent = 0;
+        }
+        let mut reports = Vec::new();
+        // an indexable in several bubbles is crawled once, for the first
+        let mut seen = HashSet::new();
+        let due = expansion
+            .indexables()
+            .filter(|(indexable, _)| seen.insert(key(indexable)) && self.due(indexable) <= now);
+        let mut due = due.collect::<Vec<_>>();
+        due.sort_by_key(|(indexable, _)| self.due(indexable));
+
+        for (indexable, reach) in due {
+            let remaining = self.remaining_budget(now);
+            if remaining == 0 {
+                break;
+            }
+            let limited = crawler
+                .clone()
+                .with_max_pages(crawler.max_pages().min(remaining));
+            let report = limited.recrawl_expanded(fetcher, index, expansion, indexable, reach);
+            self.budget_spent += report.fetched().len();
+
+            let cut_short = remaining < crawler.max_pages() && report.fetched().len() >= remaining;
+            if !cut_short {
+                let changed = if report.changed().is_empty() {
+                    0.0
+                } else {
+                    1.0
+                };
+                let history = self.histories.entry(key(indexable)).or_default();
+                if history.last_crawl.is_some() {
+                    history.change_rate = Some(match history.change_rate {
+                        Some(rate) => rate + CHANGE_RATE_SMOOTHING * (changed - rate),
+                        None => changed,
+                    });
+                }
+                history.last_crawl = Some(now);
+            }
+            reports.push((indexable.uri().to_owned(), report));
+        }
+        reports
+    }
+
+    fn history(&self, indexable: &Indexable) -> Option<&History> {
+        self.histories.get(&key(indexable))
+    }
+}
+
+// written to disk, so it mustn't depend on how types are named in code
+fn key(indexable: &Indexable) -> String {
+    format!("{} {}", indexable.scope().as_str(), indexable.uri())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        bubble::{Bubble, Limits},
+        crawl::tests::MemoryFetcher,
+    };
+
+    use super::*;
+
+    const DAY: u64 = 24 * 60 * 60;
+
+    fn bubble() -> Bubble {
+        Bubble::parse_html(
+            r#"<html><head><title>Test</title></head><body>
+            <a class="bubble-search-site" href="https://news.example.com" data-refresh="daily">News</a>
+            <a class="bubble-search-site" href="https://docs.example.com" data-refresh="monthly">Docs</a>
+        </body></html>"#,
+        )
+        .unwrap()
+    }
+
+    fn fetcher() -> MemoryFetcher {
+        MemoryFetcher::new(&[
+            ("https://news.example.com", r#"<a href="/today">Today</a>"#),
+            ("https://news.example.com/today", "News"),
+            ("https://docs.example.com", "Docs"),
+        ])
+    }
+
+    fn uris(upcoming: &[ScheduledCrawl]) -> Vec<(&str, u64)> {
+        upcoming
+            .iter()
+            .map(|crawl| (crawl.uri().as_str(), crawl.due()))
+            .collect()
+    }
+
+    #[test]
+    fn test_everything_due_at_first() {
+        let scheduler = Scheduler::new();
+        assert_eq!(
+            uris(&scheduler.upcoming(&Expansion::new(&bubble()))),
+            vec![
+                ("https://docs.example.com", 0),
+                ("https://news.example.com", 0)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tick_schedules_by_declared_refresh() {
+        let mut scheduler = Scheduler::new();
+        let mut index = Index::new();
+        let reports = scheduler.tick(
+            &Crawler::new(),
+            &fetcher(),
+            &mut index,
+            &Expansion::new(&bubble()),
+            1000,
+        );
+        assert_eq!(reports.len(), 2);
+        assert_eq!(index.len(), 3);
+        // a first crawl says nothing about how often content changes
+        assert_eq!(
+            uris(&scheduler.upcoming(&Expansion::new(&bubble()))),
+            vec![
+                ("https://news.example.com", 1000 + DAY),
+                ("https://docs.example.com", 1000 + 30 * DAY)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_changed_content_shortens_interval() {
+        let mut scheduler = Scheduler::new();
+        let mut index = Index::new();
+        let bubble = bubble();
+        scheduler.tick(
+            &Crawler::new(),
+            &fetcher(),
+            &mut index,
+            &Expansion::new(&bubble),
+            0,
+        );
+        let mut changed = fetcher();
+        changed.insert("https://news.example.com/today", "Other news");
+        scheduler.tick(
+            &Crawler::new(),
+            &changed,
+            &mut index,
+            &Expansion::new(&bubble),
+            DAY,
+        );
+        assert_eq!(scheduler.interval(&bubble.indexables()[0]), DAY / 2);
+        assert_eq!(scheduler.interval(&bubble.indexables()[1]), 30 * DAY);
+    }
+
+    #[test]
+    fn test_unchanged_content_backs_off() {
+        let mut scheduler = Scheduler::new();
+        let mut index = Index::new();
+        let bubble = bubble();
+        let news = &bubble.indexables()[0];
+        let mut now = 0;
+        for _ in 0..10 {
+            scheduler.tick(
+                &Crawler::new(),
+                &fetcher(),
+                &mut index,
+                &Expansion::new(&bubble),
+                now,
+            );
+            now = scheduler.due(news);
+        }
+        assert_eq!(scheduler.interval(news), 4 * DAY);
+    }
+
+    #[test]
+    fn test_tick_crawls_expanded_bubbles() {
+        let root = Bubble::parse_html(
+            r#"<html><head><title>Root</title></head><body>
+            <a class="bubble-search-site" href="https://news.example.com" data-refresh="daily">News</a>
+            <a class="bubble-expand" href="https://friend.org/bubble">Friend</a>
+            <a class="bubble-exclude-path" href="https://docs.example.com/old">Old</a>
+        </body></html>"#,
+        )
+        .unwrap();
+        let expansion = Expansion::resolve(&root, &Limits::default(), |_| {
+            Bubble::parse_html(
+                r#"<html><head><title>Friend</title></head><body>
+                <a class="bubble-search-site" href="https://docs.example.com">Docs</a>
+                <a class="bubble-search-site" href="https://news.example.com">News</a>
+            </body></html>"#,
+            )
+        });
+        let mut fetcher = fetcher();
+        fetcher.insert("https://docs.example.com", r#"<a href="/old">Old</a>"#);
+        fetcher.insert("https://docs.example.com/old", "Old docs");
+        let mut scheduler = Scheduler::new();
+        let mut index = Index::new();
+        let reports = scheduler.tick(&Crawler::new(), &fetcher, &mut index, &expansion, 0);
+        // news is in both bubbles but crawled once, and the root's exclude
+        // applies to what the friend brings in
+        assert_eq!(
+            reports
+                .iter()
+                .map(|(uri, _)| uri.as_str())
+                .collect::<Vec<_>>(),
+            vec!["https://news.example.com", "https://docs.example.com"]
+        );
+        assert_eq!(
+            index.uris().map(|uri| uri.as_str()).collect::<Vec<_>>(),
+            vec![
+                "https://docs.example.com",
+                "https://news.example.com",
+                "https://news.example.com/today"
+            ]
+        );
+        assert_eq!(
+            uris(&scheduler.upcoming(&expansion)),
+            vec![
+                ("https://news.example.com", DAY),
+                ("https://docs.example.com", 7 * DAY)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tick_skips_what_is_not_due() {
+        let mut scheduler = Scheduler::new();
+        let mut index = Index::new();
+        let bubble = bubble();
+        scheduler.tick(
+            &Crawler::new(),
+            &fetcher(),
+            &mut index,
+            &Expansion::new(&bubble),
+            0,
+        );
+        let reports = scheduler.tick(
+            &Crawler::new(),
+            &fetcher(),
+            &mut index,
+            &Expansion::new(&bubble),
+            DAY,
+        );
+        assert_eq!(
+            reports
+                .iter()
+                .map(|(uri, _)| uri.as_str())
+                .collect::<Vec<_>>(),
+            vec!["https://news.example.com"]
+        );
+    }
+
+    #[test]
+    fn test_budget_limits_crawling() {
+        let mut scheduler = Scheduler::new().with_policy(Policy {
+            budget: 2,
+            ..Policy::default()
+        });
+        let mut index = Index::new();
+        let bubble = bubble();
+        let reports = scheduler.tick(
+            &Crawler::new(),
+            &fetcher(),
+            &mut index,
+            &Expansion::new(&bubble),
+            0,
+        );
+        assert_eq!(
+            reports
+                .iter()
+                .map(|(_, report)| report.fetched().len())
+                .sum::<usize>(),
+            2
+        );
+        assert_eq!(scheduler.remaining_budget(0), 0);
+        assert_eq!(scheduler.next_wakeup(&Expansion::new(&bubble), 0), DAY);
+        assert_eq!(scheduler.remaining_budget(DAY), 2);
+    }
+
+    #[test]
+    fn test_crawl_cut_short_by_budget_stays_due() {
+        let mut scheduler = Scheduler::new().with_policy(Policy {
+            budget: 1,
+            ..Policy::default()
+        });
+        let mut index = Index::new();
+        let bubble = bubble();
+        let reports = scheduler.tick(
+            &Crawler::new(),
+            &fetcher(),
+            &mut index,
+            &Expansion::new(&bubble),
+            0,
+        );
+        assert_eq!(reports.len(), 1);
+        // news was cut short before today's page, docs weren't reached
+        assert_eq!(reports[0].0.as_str(), "https://news.example.com");
+        assert!(scheduler
+            .upcoming(&Expansion::new(&bubble))
+            .iter()
+            .all(|crawl| crawl.due() == 0));
+        assert_eq!(scheduler.next_wakeup(&Expansion::new(&bubble), 0), DAY);
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let path =
+            std::env::temp_dir().join(format!("bubble-schedule-{}.json", std::process::id()));
+        let mut scheduler = Scheduler::new();
+        let mut index = Index::new();
+        scheduler.tick(
+            &Crawler::new(),
+            &fetcher(),
+            &mut index,
+            &Expansion::new(&bubble()),
+            1000,
+        );
+        scheduler.save(&path).unwrap();
+        let loaded = Scheduler::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(
+            loaded.upcoming(&Expansion::new(&bubble())),
+            scheduler.upcoming(&Expansion::new(&bubble()))
+        );
+    }
+
+    #[test]
+    fn test_history_keys_are_stable() {
+        let mut scheduler = Scheduler::new();
+        let mut index = Index::new();
+        scheduler.tick(
+            &Crawler::new(),
+            &fetcher(),
+            &mut index,
+            &Expansion::new(&bubble()),
+            1000,
+        );
+        assert_eq!(
+            scheduler.histories.keys().collect::<Vec<_>>(),
+            vec![
+                "site https://docs.example.com",
+                "site https://news.example.com"
+            ]
+        );
+    }
+}
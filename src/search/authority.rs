@@ -0,0 +1,112 @@
+use std::collections::BTreeMap;
+
+use super::index::DocId;
+
+// chance of following a link rather than jumping to a random document
+const DAMPING: f64 = 0.85;
+const ITERATIONS: usize = 30;
+
+// links between pages of one site are mostly navigation, so they vouch for
+// a page much less than a link from another site in the bubble does
+const SAME_SITE_WEIGHT: f64 = 0.2;
+
+/// A link between two documents, and whether it stays within a site.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) struct Link {
+    pub(super) from: DocId,
+    pub(super) to: DocId,
+    pub(super) same_site: bool,
+}
+
+/// PageRank over the given documents, scaled so the average document has
+/// an authority of 1. Links to documents outside the graph are ignored.
+pub(super) fn authority(documents: &[DocId], links: &[Link]) -> BTreeMap<DocId, f64> {
+    if documents.is_empty() {
+        return BTreeMap::new();
+    }
+    let n = documents.len() as f64;
+    let mut outgoing: BTreeMap<DocId, Vec<(DocId, f64)>> =
+        documents.iter().map(|id| (*id, Vec::new())).collect();
+    for link in links {
+        if link.from == link.to || !outgoing.contains_key(&link.to) {
+            continue;
+        }
+        if let Some(targets) = outgoing.get_mut(&link.from) {
+            let weight = if link.same_site {
+                SAME_SITE_WEIGHT
+            } else {
+                1.0
+            };
+            targets.push((link.to, weight));
+        }
+    }
+
+    let mut rank: BTreeMap<DocId, f64> = documents.iter().map(|id| (*id, 1.0 / n)).collect();
+    for _ in 0..ITERATIONS {
+        // documents without links spread their rank over everything
+        let dangling: f64 = outgoing
+            .iter()
+            .filter(|(_, targets)| targets.is_empty())
+            .map(|(id, _)| rank[id])
+            .sum();
+        let base = (1.0 - DAMPING) / n + DAMPING * dangling / n;
+        let mut next: BTreeMap<DocId, f64> = documents.iter().map(|id| (*id, base)).collect();
+        for (id, targets) in &outgoing {
+            let total: f64 = targets.iter().map(|(_, weight)| weight).sum();
+            for (target, weight) in targets {
+                *next.get_mut(target).expect("targets are documents") +=
+                    DAMPING * rank[id] * weight / total;
+            }
+        }
+        rank = next;
+    }
+    rank.into_iter().map(|(id, rank)| (id, rank * n)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn link(from: DocId, to: DocId, same_site: bool) -> Link {
+        Link {
+            from,
+            to,
+            same_site,
+        }
+    }
+
+    #[test]
+    fn test_without_links_everything_is_average() {
+        let authority = authority(&[1, 2, 3], &[]);
+        for rank in authority.values() {
+            assert!((rank - 1.0).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn test_linked_document_has_more_authority() {
+        let authority = authority(&[1, 2, 3], &[link(1, 3, false), link(2, 3, false)]);
+        assert!(authority[&3] > authority[&1]);
+        assert!((authority.values().sum::<f64>() - 3.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_other_sites_count_more() {
+        let authority = authority(
+            &[1, 2, 3, 4],
+            &[
+                link(1, 2, true),
+                link(1, 3, false),
+                link(4, 2, true),
+                link(4, 3, false),
+            ],
+        );
+        assert!(authority[&3] > authority[&2]);
+    }
+
+    #[test]
+    fn test_links_leaving_the_graph_are_ignored() {
+        let authority = authority(&[1, 2], &[link(1, 5, false), link(1, 1, false)]);
+        assert!((authority[&1] - authority[&2]).abs() < 1e-9);
+    }
+}
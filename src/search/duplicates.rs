@@ -0,0 +1,143 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::{Deserialize, Serialize};
+
+use super::{index::DocId, tokenize::tokenize};
+
+// words per shingle; longer shingles make reordered text look less alike
+const SHINGLE_WORDS: usize = 3;
+
+/// Fingerprints at most this many bits apart are near-duplicates.
+pub const MAX_DISTANCE: u32 = 7;
+
+// with MAX_DISTANCE + 1 bands, near-duplicates always agree on one band
+const BANDS: usize = MAX_DISTANCE as usize + 1;
+const BAND_BITS: usize = 64 / BANDS;
+
+/// A 64 bit SimHash of the text's word shingles. Texts that are too short to
+/// say anything about have no fingerprint.
+pub fn simhash(text: &str) -> Option<u64> {
+    let terms = tokenize(text);
+    if terms.len() < SHINGLE_WORDS {
+        return None;
+    }
+    let mut weights = [0i64; 64];
+    for shingle in terms.windows(SHINGLE_WORDS) {
+        let hash = fnv1a(&shingle.join(" "));
+        for (bit, weight) in weights.iter_mut().enumerate() {
+            *weight += if hash & (1 << bit) != 0 { 1 } else { -1 };
+        }
+    }
+    Some(
+        weights
+            .iter()
+            .enumerate()
+            .filter(|(_, weight)| **weight > 0)
+            .fold(0, |fingerprint, (bit, _)| fingerprint | (1 << bit)),
+    )
+}
+
+pub fn is_near_duplicate(a: u64, b: u64) -> bool {
+    (a ^ b).count_ones() <= MAX_DISTANCE
+}
+
+// a hash that stays the same between builds, as fingerprints are stored
+fn fnv1a(text: &str) -> u64 {
+    text.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Finds documents with similar fingerprints without comparing them all.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub(super) struct FingerprintIndex {
+    bands: [BTreeMap<u8, BTreeSet<DocId>>; BANDS],
+}
+
+impl FingerprintIndex {
+    pub(super) fn insert(&mut self, id: DocId, fingerprint: u64) {
+        for (band, key) in band_keys(fingerprint) {
+            self.bands[band].entry(key).or_default().insert(id);
+        }
+    }
+
+    pub(super) fn remove(&mut self, id: DocId, fingerprint: u64) {
+        for (band, key) in band_keys(fingerprint) {
+            if let Some(ids) = self.bands[band].get_mut(&key) {
+                ids.remove(&id);
+                if ids.is_empty() {
+                    self.bands[band].remove(&key);
+                }
+            }
+        }
+    }
+
+    /// Documents that share a band with the fingerprint. The caller checks
+    /// the actual distance.
+    pub(super) fn candidates(&self, fingerprint: u64) -> BTreeSet<DocId> {
+        band_keys(fingerprint)
+            .filter_map(|(band, key)| self.bands[band].get(&key))
+            .flatten()
+            .copied()
+            .collect()
+    }
+}
+
+fn band_keys(fingerprint: u64) -> impl Iterator<Item = (usize, u8)> {
+    (0..BANDS).map(move |band| (band, (fingerprint >> (band * BAND_BITS)) as u8))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ARTICLE: &str = "HTML forms let users submit data to a server. A form \
+        contains input elements such as text fields, checkboxes, radio buttons and \
+        submit buttons. When the user presses submit, the browser collects the name \
+        and value of every successful control and sends them to the URL in the \
+        action attribute, using the HTTP method given in the method attribute. \
+        Forms that use GET put the values in the query string, which makes them \
+        easy to bookmark but unsuitable for passwords or large amounts of data. \
+        Forms that use POST send the values in the request body instead. Labels \
+        should be associated with their controls, so that screen readers can \
+        announce them and clicking the label focuses the field.";
+
+    #[test]
+    fn test_identical_text_same_fingerprint() {
+        assert_eq!(simhash(ARTICLE), simhash(&ARTICLE.to_uppercase()));
+    }
+
+    #[test]
+    fn test_small_edit_is_near_duplicate() {
+        let edited = format!("{ARTICLE} Print this page.");
+        let a = simhash(ARTICLE).unwrap();
+        let b = simhash(&edited).unwrap();
+        assert!(is_near_duplicate(a, b));
+    }
+
+    #[test]
+    fn test_different_text_is_not_near_duplicate() {
+        let other = "Rust is a systems programming language focused on safety, \
+            speed and concurrency. Ownership and borrowing rules are checked at \
+            compile time, so many memory bugs never make it into a program.";
+        let a = simhash(ARTICLE).unwrap();
+        let b = simhash(other).unwrap();
+        assert!(!is_near_duplicate(a, b));
+    }
+
+    #[test]
+    fn test_short_text_has_no_fingerprint() {
+        assert_eq!(simhash("too short"), None);
+    }
+
+    #[test]
+    fn test_candidates() {
+        let mut index = FingerprintIndex::default();
+        index.insert(1, 0b1011);
+        index.insert(2, !0xff | 0b1001);
+        index.insert(3, u64::MAX);
+        assert_eq!(index.candidates(0b1001), BTreeSet::from([1, 2]));
+        index.remove(2, !0xff | 0b1001);
+        assert_eq!(index.candidates(0b1001), BTreeSet::from([1]));
+    }
+}
@@ -0,0 +1,1315 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    fs,
+    io::ErrorKind,
+    path::Path,
+};
+
+use iri_string::types::{IriAbsoluteStr, IriAbsoluteString};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    bubble::{Bubble, BubbleReference, Expansion, Reach},
+    document::Document,
+    topic::Wikidata,
+};
+
+use super::{
+    authority::{authority, Link},
+    duplicates::{is_near_duplicate, simhash, FingerprintIndex},
+    parse::QueryParseError,
+    query::{Candidate, Clause, Query},
+    ranking::{Field, Ranking},
+    snippet::{Snippet, SNIPPET_WORDS},
+    tokenize::tokenize,
+    topics::{expand_query, name_clause, topic_names, Names},
+};
+
+pub type DocId = u32;
+
+// positions of a term within each field of a single document
+type Positions = BTreeMap<Field, Vec<u32>>;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum IndexError {
+    #[error("Could not access index file: {0}")]
+    Io(String),
+    #[error("Could not read index file: {0}")]
+    Format(String),
+    #[error("Not collecting garbage while bubble {0} can't be expanded")]
+    IncompleteExpansion(String),
+}
+
+impl From<std::io::Error> for IndexError {
+    fn from(error: std::io::Error) -> Self {
+        IndexError::Io(error.to_string())
+    }
+}
+
+impl From<serde_json::Error> for IndexError {
+    fn from(error: serde_json::Error) -> Self {
+        IndexError::Format(error.to_string())
+    }
+}
+
+/// An inverted index over crawled documents. Postings keep term positions
+/// per field so phrases can be matched.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Index {
+    next_id: DocId,
+    ids: BTreeMap<IriAbsoluteString, DocId>,
+    documents: BTreeMap<DocId, StoredDocument>,
+    postings: BTreeMap<String, BTreeMap<DocId, Positions>>,
+    total_lengths: [u64; 4],
+    // documents purged because they fell out of scope; crawls don't bring
+    // them back until a bubble admits them again
+    #[serde(default)]
+    tombstones: BTreeSet<IriAbsoluteString>,
+    #[serde(default)]
+    fingerprints: FingerprintIndex,
+    #[serde(skip)]
+    ranking: Ranking,
+    // labels and aliases of topics, for query expansion
+    #[serde(skip)]
+    wikidata: Option<Wikidata>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct StoredDocument {
+    document: Document,
+    lengths: [u32; 4],
+    // SimHash of the body, to spot near-duplicates
+    #[serde(default)]
+    fingerprint: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchResult {
+    uri: IriAbsoluteString,
+    title: String,
+    score: f64,
+    snippet: Snippet,
+    // near-duplicates collapsed into this result
+    duplicates: Vec<IriAbsoluteString>,
+    // the expanded bubbles through which the result is included, empty if
+    // the searched bubble includes it itself
+    via: Vec<BubbleReference>,
+    // what curators noted about the indexables including the result
+    notes: Vec<String>,
+    tags: Vec<String>,
+}
+
+impl SearchResult {
+    pub fn uri(&self) -> &IriAbsoluteStr {
+        &self.uri
+    }
+
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    pub fn score(&self) -> f64 {
+        self.score
+    }
+
+    pub fn snippet(&self) -> &Snippet {
+        &self.snippet
+    }
+
+    pub fn duplicates(&self) -> &[IriAbsoluteString] {
+        &self.duplicates
+    }
+
+    pub fn via(&self) -> &[BubbleReference] {
+        &self.via
+    }
+
+    pub fn notes(&self) -> &[String] {
+        &self.notes
+    }
+
+    pub fn tags(&self) -> &[String] {
+        &self.tags
+    }
+}
+
+impl Index {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_ranking(mut self, ranking: Ranking) -> Self {
+        self.ranking = ranking;
+        self
+    }
+
+    pub fn with_wikidata(mut self, wikidata: Wikidata) -> Self {
+        self.wikidata = Some(wikidata);
+        self
+    }
+
+    /// Load an index from disk. A missing file is an empty index.
+    pub fn load(path: &Path) -> Result<Self, IndexError> {
+        match fs::read(path) {
+            Ok(data) => Ok(serde_json::from_slice(&data)?),
+            Err(error) if error.kind() == ErrorKind::NotFound => Ok(Self::new()),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    /// Write the index to disk, replacing any previous version atomically.
+    pub fn save(&self, path: &Path) -> Result<(), IndexError> {
+        let temporary = path.with_extension("tmp");
+        fs::write(&temporary, serde_json::to_vec(self)?)?;
+        fs::rename(&temporary, path)?;
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.documents.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.documents.is_empty()
+    }
+
+    pub fn uris(&self) -> impl Iterator<Item = &IriAbsoluteStr> {
+        self.ids.keys().map(|uri| uri.as_ref())
+    }
+
+    pub fn contains(&self, uri: &IriAbsoluteStr) -> bool {
+        self.ids.contains_key(uri)
+    }
+
+    pub fn document(&self, uri: &IriAbsoluteStr) -> Option<&Document> {
+        self.ids.get(uri).map(|id| &self.documents[id].document)
+    }
+
+    /// Add a document, replacing any earlier version with the same URI.
+    pub fn add(&mut self, document: Document) {
+        self.remove(document.uri());
+        let id = self.next_id;
+        self.next_id += 1;
+
+        let mut lengths = [0; 4];
+        for field in Field::ALL {
+            let mut position = 0;
+            for text in field_texts(&document, field) {
+                for term in tokenize(text) {
+                    self.postings
+                        .entry(term)
+                        .or_default()
+                        .entry(id)
+                        .or_default()
+                        .entry(field)
+                        .or_default()
+                        .push(position);
+                    position += 1;
+                }
+                // leave a gap so phrases don't match across separate headings
+                position += 1;
+            }
+            let length = position - field_texts(&document, field).len() as u32;
+            lengths[field.slot()] = length;
+            self.total_lengths[field.slot()] += length as u64;
+        }
+
+        let fingerprint = simhash(document.body());
+        if let Some(fingerprint) = fingerprint {
+            self.fingerprints.insert(id, fingerprint);
+        }
+        self.ids.insert(document.uri().to_owned(), id);
+        self.documents.insert(
+            id,
+            StoredDocument {
+                document,
+                lengths,
+                fingerprint,
+            },
+        );
+    }
+
+    /// Remove a document. Returns whether it was in the index.
+    pub fn remove(&mut self, uri: &IriAbsoluteStr) -> bool {
+        let Some(id) = self.ids.remove(uri) else {
+            return false;
+        };
+        let stored = self
+            .documents
+            .remove(&id)
+            .expect("ids and documents are kept in sync");
+        if let Some(fingerprint) = stored.fingerprint {
+            self.fingerprints.remove(id, fingerprint);
+        }
+        for (slot, length) in stored.lengths.iter().enumerate() {
+            self.total_lengths[slot] -= *length as u64;
+        }
+        let mut terms = Field::ALL
+            .iter()
+            .flat_map(|field| field_texts(&stored.document, *field))
+            .flat_map(tokenize)
+            .collect::<Vec<_>>();
+        terms.sort();
+        terms.dedup();
+        for term in terms {
+            if let Some(postings) = self.postings.get_mut(&term) {
+                postings.remove(&id);
+                if postings.is_empty() {
+                    self.postings.remove(&term);
+                }
+            }
+        }
+        true
+    }
+
+    /// Other documents with nearly the same text, or that are linked to this
+    /// one as canonical version.
+    pub fn near_duplicates(&self, uri: &IriAbsoluteStr) -> Vec<&IriAbsoluteStr> {
+        let Some(id) = self.ids.get(uri) else {
+            return Vec::new();
+        };
+        let stored = &self.documents[id];
+        let mut candidates = stored
+            .fingerprint
+            .map(|fingerprint| self.fingerprints.candidates(fingerprint))
+            .unwrap_or_default();
+        let canonical = stored.document.canonical().unwrap_or(uri);
+        candidates.extend(self.ids.get(canonical));
+        candidates.extend(
+            self.documents
+                .iter()
+                .filter(|(_, other)| other.document.canonical() == Some(canonical))
+                .map(|(other, _)| *other),
+        );
+        candidates
+            .into_iter()
+            .filter(|other| other != id && self.are_duplicates(*id, *other))
+            .map(|other| self.documents[&other].document.uri())
+            .collect()
+    }
+
+    fn are_duplicates(&self, a: DocId, b: DocId) -> bool {
+        let (a, b) = (&self.documents[&a], &self.documents[&b]);
+        let canonical = |stored: &StoredDocument| {
+            stored
+                .document
+                .canonical()
+                .unwrap_or(stored.document.uri())
+                .to_owned()
+        };
+        canonical(a) == canonical(b)
+            || matches!((a.fingerprint, b.fingerprint), (Some(a), Some(b)) if is_near_duplicate(a, b))
+    }
+
+    // group near-duplicates among ranked documents, keeping the rank of the
+    // best one; each group is led by the canonical version if it's there
+    fn collapse(&self, ranked: Vec<(DocId, f64)>) -> Vec<(DocId, f64, Vec<DocId>)> {
+        let mut groups: Vec<(f64, Vec<DocId>)> = Vec::new();
+        for (id, score) in ranked {
+            match groups.iter_mut().find(|(_, members)| {
+                members
+                    .iter()
+                    .any(|member| self.are_duplicates(*member, id))
+            }) {
+                Some((_, members)) => members.push(id),
+                None => groups.push((score, vec![id])),
+            }
+        }
+        groups
+            .into_iter()
+            .map(|(score, mut members)| {
+                let declared = members.iter().position(|member| {
+                    let uri = self.documents[member].document.uri();
+                    members
+                        .iter()
+                        .any(|other| self.documents[other].document.canonical() == Some(uri))
+                });
+                let leader = members.remove(declared.unwrap_or(0));
+                (leader, score, members)
+            })
+            .collect()
+    }
+
+    /// Remove a document and leave a tombstone for it, so crawls skip it
+    /// from now on. Tombstones hold for every bubble sharing the index, so
+    /// only purge what none of them admit.
+    pub fn purge(&mut self, uri: &IriAbsoluteStr) -> bool {
+        self.tombstones.insert(uri.to_owned());
+        self.remove(uri)
+    }
+
+    pub fn is_tombstoned(&self, uri: &IriAbsoluteStr) -> bool {
+        self.tombstones.contains(uri)
+    }
+
+    pub fn tombstones(&self) -> impl Iterator<Item = &IriAbsoluteStr> {
+        self.tombstones.iter().map(|uri| uri.as_ref())
+    }
+
+    /// Lift the tombstone for a URI, so it can be crawled again.
+    pub fn revive(&mut self, uri: &IriAbsoluteStr) -> bool {
+        self.tombstones.remove(uri)
+    }
+
+    /// Purge every document that none of the expansions sharing this index
+    /// admit, excludes included, so a crawl from an outdated bubble doesn't
+    /// bring it back. A bubble that admits it again lifts the tombstone when
+    /// reindexed. Returns the purged URIs.
+    ///
+    /// A bubble that couldn't be expanded, say because it was unreachable,
+    /// would make its documents look unused, so nothing is removed then.
+    pub fn collect_garbage(
+        &mut self,
+        expansions: &[&Expansion],
+    ) -> Result<Vec<IriAbsoluteString>, IndexError> {
+        if let Some((uri, _)) = expansions
+            .iter()
+            .find_map(|expansion| expansion.unresolved().first())
+        {
+            return Err(IndexError::IncompleteExpansion(uri.to_string()));
+        }
+        let garbage = self
+            .uris()
+            .filter(|uri| !expansions.iter().any(|expansion| expansion.admits(uri)))
+            .map(|uri| uri.to_owned())
+            .collect::<Vec<_>>();
+        for uri in &garbage {
+            self.purge(uri);
+        }
+        Ok(garbage)
+    }
+
+    /// Search the documents admitted by the bubble, best matches first.
+    pub fn search(
+        &self,
+        bubble: &Bubble,
+        query: &str,
+    ) -> Result<Vec<SearchResult>, QueryParseError> {
+        self.search_expanded(&Expansion::new(bubble), query)
+    }
+
+    /// Search the documents admitted by any bubble in the expansion. Those
+    /// from expanded bubbles count for less the further away they are.
+    pub fn search_expanded(
+        &self,
+        expansion: &Expansion,
+        query: &str,
+    ) -> Result<Vec<SearchResult>, QueryParseError> {
+        Ok(self.search_query(expansion, &Query::parse(query)?))
+    }
+
+    /// Names of the searched bubble's topics in the query match their other
+    /// names too, and documents mentioning the topics rank higher.
+    pub fn search_query(&self, expansion: &Expansion, query: &Query) -> Vec<SearchResult> {
+        if query.is_empty() {
+            return Vec::new();
+        }
+        let topics = topic_names(expansion.root().topic(), self.wikidata.as_ref());
+        let query = &expand_query(query, &topics);
+        let candidates = self
+            .documents
+            .iter()
+            .filter_map(|(id, stored)| {
+                let admissions = expansion.admitting(stored.document.uri());
+                (!admissions.is_empty()).then_some((
+                    *id,
+                    Candidate {
+                        document: &stored.document,
+                        admissions,
+                    },
+                ))
+            })
+            .collect::<BTreeMap<_, _>>();
+
+        let mut frequencies = HashMap::new();
+        for clause in query.scoring_clauses() {
+            frequencies
+                .entry(clause)
+                .or_insert_with(|| self.clause_frequencies(clause));
+        }
+        let matching = self.matching(query, &candidates, &mut frequencies);
+
+        let average_lengths = self.average_lengths();
+        let mut scores: BTreeMap<DocId, f64> = matching.iter().map(|id| (*id, 0.0)).collect();
+        for clause in query.scoring_clauses() {
+            let frequencies = &frequencies[clause];
+            let idf = self.ranking.idf(self.documents.len(), frequencies.len());
+            for (id, score) in scores.iter_mut() {
+                if let Some(frequencies) = frequencies.get(id) {
+                    let lengths = &self.documents[id].lengths;
+                    *score += self
+                        .ranking
+                        .score(idf, frequencies, lengths, &average_lengths);
+                }
+            }
+        }
+
+        let authority = self.authority(&candidates);
+        let mentions = self.mentions(&topics, &average_lengths);
+        let mut provenance = BTreeMap::new();
+        for (id, score) in scores.iter_mut() {
+            let (curated, reach) = self.curation(&candidates[id]);
+            let mention = mentions.get(id).copied().unwrap_or(0.0);
+            *score *= curated * self.ranking.boost(authority[id]) * self.ranking.topical(mention);
+            provenance.insert(*id, reach);
+        }
+
+        let highlighted = query
+            .scoring_clauses()
+            .into_iter()
+            .flat_map(|clause| match clause {
+                Clause::Term(term) => vec![term.clone()],
+                Clause::Phrase(terms) => terms.clone(),
+            })
+            .collect::<HashSet<_>>();
+        let mut ranked = scores.into_iter().collect::<Vec<_>>();
+        ranked.sort_by(|(a_id, a), (b_id, b)| {
+            b.total_cmp(a).then_with(|| {
+                self.documents[a_id]
+                    .document
+                    .uri()
+                    .cmp(self.documents[b_id].document.uri())
+            })
+        });
+        self.collapse(ranked)
+            .into_iter()
+            .map(|(id, score, duplicates)| {
+                let document = &self.documents[&id].document;
+                let (notes, tags) = annotations(&candidates[&id]);
+                SearchResult {
+                    uri: document.uri().to_owned(),
+                    title: document.title().to_string(),
+                    score,
+                    snippet: Snippet::new(document.body(), &highlighted, SNIPPET_WORDS),
+                    duplicates: duplicates
+                        .iter()
+                        .map(|duplicate| self.documents[duplicate].document.uri().to_owned())
+                        .collect(),
+                    via: provenance[&id].via().to_vec(),
+                    notes,
+                    tags,
+                }
+            })
+            .collect()
+    }
+
+    // a document admitted several times counts with the most trusted bubble
+    // and the best boost it has there
+    fn curation<'a>(&self, candidate: &Candidate<'a>) -> (f64, &'a Reach) {
+        candidate
+            .admissions
+            .iter()
+            .map(|admission| {
+                let boost = admission
+                    .indexables()
+                    .iter()
+                    .map(|indexable| indexable.boost())
+                    .fold(f64::MIN, f64::max);
+                (
+                    self.ranking.trust(admission.reach()) * boost,
+                    admission.reach(),
+                )
+            })
+            .max_by(|(a, _), (b, _)| a.total_cmp(b))
+            .expect("candidates are admitted at least once")
+    }
+
+    // How strongly documents mention the topic they mention most, from 0 to
+    // 1. Each name counts like a clause; URLs don't count, since "html"
+    // would be in every .html address.
+    fn mentions(&self, topics: &[Names], average_lengths: &[f64; 4]) -> BTreeMap<DocId, f64> {
+        let mut mentions = BTreeMap::new();
+        for names in topics {
+            let mut topic_frequencies: BTreeMap<DocId, [u32; 4]> = BTreeMap::new();
+            for name in names {
+                for (id, frequencies) in self.clause_frequencies(&name_clause(name)) {
+                    let total = topic_frequencies.entry(id).or_default();
+                    for field in [Field::Title, Field::Headings, Field::Body] {
+                        total[field.slot()] += frequencies[field.slot()];
+                    }
+                }
+            }
+            for (id, frequencies) in topic_frequencies {
+                let lengths = &self.documents[&id].lengths;
+                let mention = self
+                    .ranking
+                    .score(1.0, &frequencies, lengths, average_lengths);
+                let best = mentions.entry(id).or_insert(0.0);
+                *best = mention.max(*best);
+            }
+        }
+        mentions
+    }
+
+    // PageRank over the links between the candidates, so only pages in the
+    // bubble confer authority
+    fn authority(&self, candidates: &BTreeMap<DocId, Candidate>) -> BTreeMap<DocId, f64> {
+        let links = candidates
+            .iter()
+            .flat_map(|(from, candidate)| {
+                let from_host = host(candidate.document.uri());
+                candidate.document.links().iter().filter_map(move |target| {
+                    Some(Link {
+                        from: *from,
+                        to: *self.ids.get(target)?,
+                        same_site: from_host == host(target),
+                    })
+                })
+            })
+            .collect::<Vec<_>>();
+        authority(&candidates.keys().copied().collect::<Vec<_>>(), &links)
+    }
+
+    // the candidates that satisfy the query
+    fn matching<'a>(
+        &self,
+        query: &'a Query,
+        candidates: &BTreeMap<DocId, Candidate>,
+        frequencies: &mut HashMap<&'a Clause, BTreeMap<DocId, [u32; 4]>>,
+    ) -> BTreeSet<DocId> {
+        match query {
+            Query::Clause(clause) => frequencies
+                .entry(clause)
+                .or_insert_with(|| self.clause_frequencies(clause))
+                .keys()
+                .filter(|id| candidates.contains_key(id))
+                .copied()
+                .collect(),
+            Query::Filter(filter) => candidates
+                .iter()
+                .filter(|(_, candidate)| filter.matches(candidate))
+                .map(|(id, _)| *id)
+                .collect(),
+            Query::And(queries) => {
+                let mut result = candidates.keys().copied().collect::<BTreeSet<_>>();
+                for query in queries {
+                    let matching = self.matching(query, candidates, frequencies);
+                    result.retain(|id| matching.contains(id));
+                }
+                result
+            }
+            Query::Or(queries) => queries
+                .iter()
+                .flat_map(|query| self.matching(query, candidates, frequencies))
+                .collect(),
+            Query::Not(query) => {
+                let excluded = self.matching(query, candidates, frequencies);
+                candidates
+                    .keys()
+                    .filter(|id| !excluded.contains(id))
+                    .copied()
+                    .collect()
+            }
+        }
+    }
+
+    fn average_lengths(&self) -> [f64; 4] {
+        let count = self.documents.len().max(1) as f64;
+        self.total_lengths.map(|total| total as f64 / count)
+    }
+
+    // how often the clause occurs in each field of each matching document
+    fn clause_frequencies(&self, clause: &Clause) -> BTreeMap<DocId, [u32; 4]> {
+        match clause {
+            Clause::Term(term) => self
+                .postings
+                .get(term)
+                .map(|postings| {
+                    postings
+                        .iter()
+                        .map(|(id, positions)| {
+                            let mut frequencies = [0; 4];
+                            for (field, positions) in positions {
+                                frequencies[field.slot()] = positions.len() as u32;
+                            }
+                            (*id, frequencies)
+                        })
+                        .collect()
+                })
+                .unwrap_or_default(),
+            Clause::Phrase(terms) => self.phrase_frequencies(terms),
+        }
+    }
+
+    fn phrase_frequencies(&self, terms: &[String]) -> BTreeMap<DocId, [u32; 4]> {
+        let Some(postings) = terms
+            .iter()
+            .map(|term| self.postings.get(term))
+            .collect::<Option<Vec<_>>>()
+        else {
+            return BTreeMap::new();
+        };
+        let (first, rest) = postings.split_first().expect("phrases have terms");
+
+        let mut result = BTreeMap::new();
+        for (id, first_positions) in first.iter() {
+            let mut frequencies = [0; 4];
+            for (field, starts) in first_positions {
+                let count = starts
+                    .iter()
+                    .filter(|start| {
+                        rest.iter().enumerate().all(|(offset, postings)| {
+                            postings
+                                .get(id)
+                                .and_then(|positions| positions.get(field))
+                                .is_some_and(|positions| {
+                                    positions
+                                        .binary_search(&(**start + offset as u32 + 1))
+                                        .is_ok()
+                                })
+                        })
+                    })
+                    .count();
+                frequencies[field.slot()] = count as u32;
+            }
+            if frequencies.iter().any(|frequency| *frequency > 0) {
+                result.insert(*id, frequencies);
+            }
+        }
+        result
+    }
+}
+
+// notes and tags of the indexables admitting a candidate, each once
+fn annotations(candidate: &Candidate) -> (Vec<String>, Vec<String>) {
+    let mut notes = Vec::new();
+    let mut tags = Vec::<String>::new();
+    for indexable in candidate
+        .admissions
+        .iter()
+        .flat_map(|admission| admission.indexables())
+    {
+        if let Some(note) = indexable.note() {
+            if !notes.iter().any(|seen| seen == note) {
+                notes.push(note.to_string());
+            }
+        }
+        for tag in indexable.tags() {
+            if !tags.iter().any(|seen| seen.eq_ignore_ascii_case(tag)) {
+                tags.push(tag.clone());
+            }
+        }
+    }
+    (notes, tags)
+}
+
+fn host(uri: &IriAbsoluteStr) -> Option<String> {
+    uri.authority_components()
+        .map(|authority| authority.host().to_ascii_lowercase())
+}
+
+fn field_texts(document: &Document, field: Field) -> Vec<&str> {
+    match field {
+        Field::Title => vec![document.title()],
+        Field::Headings => document.headings().iter().map(String::as_str).collect(),
+        Field::Body => vec![document.body()],
+        Field::Url => vec![document.uri().as_str()],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bubble::Limits;
+
+    use super::*;
+
+    fn document(uri: &str, title: &str, headings: &[&str], body: &str) -> Document {
+        Document::new(
+            uri.parse().unwrap(),
+            title.to_string(),
+            headings.iter().map(|heading| heading.to_string()).collect(),
+            body.to_string(),
+        )
+    }
+
+    fn bubble() -> Bubble {
+        Bubble::parse_html(
+            r#"<html>
+            <head><title>Web</title></head>
+            <body>
+                <a class="bubble-search-site" href="https://example.com">Example</a>
+                <a class="bubble-exclude-path" href="https://example.com/spam">Spam</a>
+            </body>
+        </html>"#,
+        )
+        .unwrap()
+    }
+
+    fn uris(results: &[SearchResult]) -> Vec<&str> {
+        results.iter().map(|result| result.uri().as_str()).collect()
+    }
+
+    #[test]
+    fn test_search_ranks_title_over_body() {
+        let mut index = Index::new();
+        index.add(document(
+            "https://example.com/body",
+            "Elements",
+            &[],
+            "the forms element",
+        ));
+        index.add(document(
+            "https://example.com/title",
+            "Forms",
+            &[],
+            "the element",
+        ));
+        index.add(document(
+            "https://example.com/none",
+            "Tables",
+            &[],
+            "the table element",
+        ));
+        let results = index.search(&bubble(), "forms").unwrap();
+        assert_eq!(
+            uris(&results),
+            vec!["https://example.com/title", "https://example.com/body"]
+        );
+    }
+
+    #[test]
+    fn test_search_only_admitted_documents() {
+        let mut index = Index::new();
+        index.add(document("https://example.com/a", "Forms", &[], ""));
+        index.add(document("https://example.com/spam/a", "Forms", &[], ""));
+        index.add(document("https://another.com/a", "Forms", &[], ""));
+        let results = index.search(&bubble(), "forms").unwrap();
+        assert_eq!(uris(&results), vec!["https://example.com/a"]);
+    }
+
+    #[test]
+    fn test_search_phrase() {
+        let mut index = Index::new();
+        index.add(document(
+            "https://example.com/a",
+            "",
+            &[],
+            "html forms are useful",
+        ));
+        index.add(document(
+            "https://example.com/b",
+            "",
+            &[],
+            "forms in html are useful",
+        ));
+        let results = index.search(&bubble(), r#""html forms""#).unwrap();
+        assert_eq!(uris(&results), vec!["https://example.com/a"]);
+    }
+
+    #[test]
+    fn test_search_phrase_does_not_cross_headings() {
+        let mut index = Index::new();
+        index.add(document(
+            "https://example.com/a",
+            "",
+            &["Introduction to html", "forms"],
+            "",
+        ));
+        let results = index.search(&bubble(), r#""html forms""#).unwrap();
+        assert!(results.is_empty());
+    }
+
+    #[test]
+    fn test_search_boolean() {
+        let mut index = Index::new();
+        index.add(document("https://example.com/a", "Forms", &[], "html"));
+        index.add(document("https://example.com/b", "Forms", &[], "spam"));
+        index.add(document("https://example.com/c", "Tables", &[], "html"));
+        let search = |query| uris(&index.search(&bubble(), query).unwrap()).len();
+        assert_eq!(search("forms html"), 1);
+        assert_eq!(search("forms -spam"), 1);
+        assert_eq!(search("forms OR tables"), 3);
+        assert_eq!(search("(forms OR tables) -spam"), 2);
+        assert_eq!(search("-spam"), 2);
+    }
+
+    #[test]
+    fn test_search_filter() {
+        let mut index = Index::new();
+        index.add(document("https://example.com/a", "Forms", &[], "").with_lang("en"));
+        index.add(document("https://example.com/b", "Formulieren", &[], "").with_lang("nl"));
+        let results = index.search(&bubble(), "lang:nl").unwrap();
+        assert_eq!(uris(&results), vec!["https://example.com/b"]);
+        let results = index.search(&bubble(), "forms lang:nl").unwrap();
+        assert!(results.is_empty());
+        let results = index.search(&bubble(), "indexable:example").unwrap();
+        assert_eq!(results.len(), 2);
+    }
+
+    #[test]
+    fn test_search_notes_and_tags() {
+        let bubble = Bubble::parse_html(
+            r#"<html>
+            <head><title>Web</title></head>
+            <body>
+                <a class="bubble-search-site" href="https://example.com" data-tags="docs">Example</a>
+                <a class="bubble-search-path" href="https://example.com/learn" data-note="Great for beginners" data-tags="beginners, Docs">Learn</a>
+            </body>
+        </html>"#,
+        )
+        .unwrap();
+        let mut index = Index::new();
+        index.add(document(
+            "https://example.com/learn/forms",
+            "Forms",
+            &[],
+            "",
+        ));
+        index.add(document(
+            "https://example.com/reference/forms",
+            "Forms",
+            &[],
+            "",
+        ));
+        let results = index.search(&bubble, "forms tag:beginners").unwrap();
+        assert_eq!(uris(&results), vec!["https://example.com/learn/forms"]);
+        assert_eq!(results[0].notes(), &["Great for beginners"]);
+        assert_eq!(results[0].tags(), &["docs", "beginners"]);
+    }
+
+    #[test]
+    fn test_search_snippet() {
+        let mut index = Index::new();
+        index.add(document(
+            "https://example.com/a",
+            "Forms",
+            &[],
+            "Forms collect <input> from users.",
+        ));
+        let results = index.search(&bubble(), "input -spam").unwrap();
+        assert_eq!(
+            results[0].snippet().to_html(),
+            "Forms collect &lt;<mark>input</mark>&gt; from users."
+        );
+    }
+
+    const ARTICLE: &str = "HTML forms let users submit data to a server. A form \
+        contains input elements such as text fields, checkboxes, radio buttons and \
+        submit buttons. When the user presses submit, the browser collects the name \
+        and value of every successful control and sends them to the URL in the \
+        action attribute, using the HTTP method given in the method attribute. \
+        Forms that use GET put the values in the query string, which makes them \
+        easy to bookmark but unsuitable for passwords or large amounts of data. \
+        Forms that use POST send the values in the request body instead. Labels \
+        should be associated with their controls, so that screen readers can \
+        announce them and clicking the label focuses the field.";
+
+    #[test]
+    fn test_search_collapses_near_duplicates() {
+        let mut index = Index::new();
+        index.add(document("https://example.com/forms", "Forms", &[], ARTICLE));
+        index.add(document(
+            "https://example.com/mirror/forms",
+            "Forms",
+            &[],
+            &format!("{ARTICLE} Mirrored."),
+        ));
+        index.add(document(
+            "https://example.com/tables",
+            "Tables",
+            &[],
+            "Tables arrange data in rows and columns, and forms can contain one.",
+        ));
+        let results = index.search(&bubble(), "forms").unwrap();
+        assert_eq!(
+            uris(&results),
+            vec!["https://example.com/forms", "https://example.com/tables"]
+        );
+        assert_eq!(
+            results[0]
+                .duplicates()
+                .iter()
+                .map(|uri| uri.as_str())
+                .collect::<Vec<_>>(),
+            vec!["https://example.com/mirror/forms"]
+        );
+    }
+
+    #[test]
+    fn test_search_prefers_canonical() {
+        let mut index = Index::new();
+        index.add(document(
+            "https://example.com/article",
+            "Article",
+            &[],
+            ARTICLE,
+        ));
+        index.add(
+            document(
+                "https://example.com/article/print",
+                "Article forms forms forms",
+                &[],
+                "Printable.",
+            )
+            .with_canonical("https://example.com/article".parse().unwrap()),
+        );
+        let results = index.search(&bubble(), "forms").unwrap();
+        assert_eq!(uris(&results), vec!["https://example.com/article"]);
+        assert_eq!(
+            results[0]
+                .duplicates()
+                .iter()
+                .map(|uri| uri.as_str())
+                .collect::<Vec<_>>(),
+            vec!["https://example.com/article/print"]
+        );
+        assert_eq!(
+            index.near_duplicates(
+                &"https://example.com/article"
+                    .parse::<IriAbsoluteString>()
+                    .unwrap()
+            ),
+            vec!["https://example.com/article/print"]
+        );
+    }
+
+    #[test]
+    fn test_search_favours_linked_documents() {
+        let bubble = Bubble::parse_html(
+            r#"<html>
+            <head><title>Web</title></head>
+            <body>
+                <a class="bubble-search-site" href="https://example.com">Example</a>
+                <a class="bubble-search-site" href="https://other.org">Other</a>
+            </body>
+        </html>"#,
+        )
+        .unwrap();
+        let mut index = Index::new();
+        index.add(document("https://example.com/a", "Forms", &[], "forms"));
+        index.add(document("https://example.com/b", "Forms", &[], "forms"));
+        // a link from outside the bubble confers nothing
+        index.add(
+            document("https://spam.com/", "Spam", &[], "spam")
+                .with_links(vec!["https://example.com/a".parse().unwrap()]),
+        );
+        let results = index.search(&bubble, "forms").unwrap();
+        assert_eq!(
+            uris(&results),
+            vec!["https://example.com/a", "https://example.com/b"]
+        );
+        assert_eq!(results[0].score(), results[1].score());
+
+        index.add(
+            document("https://other.org/", "Other", &[], "links")
+                .with_links(vec!["https://example.com/b".parse().unwrap()]),
+        );
+        let results = index.search(&bubble, "forms").unwrap();
+        assert_eq!(
+            uris(&results),
+            vec!["https://example.com/b", "https://example.com/a"]
+        );
+        assert!(results[0].score() > results[1].score());
+    }
+
+    #[test]
+    fn test_search_applies_indexable_boost() {
+        let bubble = Bubble::parse_html(
+            r#"<html>
+            <head><title>Web</title></head>
+            <body>
+                <a class="bubble-search-site" href="https://docs.example.com" data-boost="2">Docs</a>
+                <a class="bubble-search-site" href="https://blog.example.com" data-boost="0.5">Blog</a>
+            </body>
+        </html>"#,
+        )
+        .unwrap();
+        let mut index = Index::new();
+        index.add(document(
+            "https://blog.example.com/forms",
+            "Forms",
+            &[],
+            "forms",
+        ));
+        index.add(document(
+            "https://docs.example.com/elements",
+            "Elements",
+            &[],
+            "forms",
+        ));
+        let results = index.search(&bubble, "forms").unwrap();
+        assert_eq!(
+            uris(&results),
+            vec![
+                "https://docs.example.com/elements",
+                "https://blog.example.com/forms"
+            ]
+        );
+    }
+
+    fn topic_bubble() -> Bubble {
+        Bubble::parse_html(
+            r#"<html>
+            <head><title>HTML</title></head>
+            <body>
+                <a class="bubble-wikidata-topic" href="https://www.wikidata.org/wiki/Q8811">HTML</a>
+                <a class="bubble-search-site" href="https://example.com">Example</a>
+            </body>
+        </html>"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_search_prefers_topic_mentions() {
+        let mut index = Index::new();
+        index.add(document(
+            "https://example.com/pdf-forms.html",
+            "Forms",
+            &[],
+            "filling in forms in a PDF viewer",
+        ));
+        index.add(document(
+            "https://example.com/html-forms",
+            "Forms",
+            &[],
+            "filling in forms in an HTML page with a few inputs",
+        ));
+        let results = index.search(&topic_bubble(), "forms").unwrap();
+        assert_eq!(
+            uris(&results),
+            vec![
+                "https://example.com/html-forms",
+                "https://example.com/pdf-forms.html"
+            ]
+        );
+
+        // the shorter page wins on text alone
+        let index = index.with_ranking(Ranking {
+            topic: 0.0,
+            ..Ranking::default()
+        });
+        let results = index.search(&topic_bubble(), "forms").unwrap();
+        assert_eq!(
+            results[0].uri().as_str(),
+            "https://example.com/pdf-forms.html"
+        );
+    }
+
+    #[test]
+    fn test_search_expands_topic_names() {
+        let wikidata = Wikidata::read(
+            r#"{"type":"item","id":"Q8811","labels":{"en":{"language":"en","value":"HTML"}},"aliases":{"en":[{"language":"en","value":"HyperText Markup Language"}]}}"#
+                .as_bytes(),
+        )
+        .unwrap();
+        let mut index = Index::new().with_wikidata(wikidata);
+        index.add(document(
+            "https://example.com/spec",
+            "The HyperText Markup Language",
+            &[],
+            "a specification",
+        ));
+        index.add(document(
+            "https://example.com/css",
+            "CSS",
+            &[],
+            "styling a page",
+        ));
+        let results = index.search(&topic_bubble(), "html").unwrap();
+        assert_eq!(uris(&results), vec!["https://example.com/spec"]);
+        let results = index
+            .search(&topic_bubble(), r#""hypertext markup language" OR css"#)
+            .unwrap();
+        assert_eq!(results.len(), 2);
+        // without the dump the alias is unknown
+        let results = Index::new().search(&topic_bubble(), "html").unwrap();
+        assert!(results.is_empty());
+    }
+
+    #[test]
+    fn test_search_expanded_bubbles_count_less() {
+        let root = Bubble::parse_html(
+            r#"<html>
+            <head><title>Web</title></head>
+            <body>
+                <a class="bubble-search-site" href="https://example.com">Example</a>
+                <a class="bubble-expand" href="https://friend.org/bubble">Friend</a>
+            </body>
+        </html>"#,
+        )
+        .unwrap();
+        let expansion = Expansion::resolve(&root, &Limits::default(), |_| {
+            Bubble::parse_html(
+                r#"<html>
+                <head><title>Friend</title></head>
+                <body>
+                    <a class="bubble-search-site" href="https://friend.org">Friend</a>
+                </body>
+            </html>"#,
+            )
+        });
+        let mut index = Index::new();
+        // the friend's page matches better, but comes from further away
+        index.add(document("https://friend.org/forms", "Forms", &[], "forms"));
+        index.add(document("https://example.com/a", "A", &[], "forms"));
+        let results = index.search_expanded(&expansion, "forms").unwrap();
+        assert_eq!(
+            uris(&results),
+            vec!["https://example.com/a", "https://friend.org/forms"]
+        );
+        assert!(results[0].via().is_empty());
+        assert_eq!(results[1].via()[0].label(), "Friend");
+        assert_eq!(
+            uris(&index.search_expanded(&expansion, "bubble:friend").unwrap()),
+            vec!["https://friend.org/forms"]
+        );
+
+        let index = index.with_ranking(Ranking {
+            expansion_decay: 1.0,
+            ..Ranking::default()
+        });
+        let results = index.search_expanded(&expansion, "forms").unwrap();
+        assert_eq!(
+            uris(&results),
+            vec!["https://friend.org/forms", "https://example.com/a"]
+        );
+        // searching the bubble alone leaves the friend's pages out
+        assert_eq!(
+            uris(&index.search(&root, "forms").unwrap()),
+            vec!["https://example.com/a"]
+        );
+    }
+
+    #[test]
+    fn test_search_parse_error() {
+        let index = Index::new();
+        assert_eq!(
+            index.search(&bubble(), "(forms"),
+            Err(QueryParseError::UnmatchedParenthesis(0))
+        );
+    }
+
+    #[test]
+    fn test_search_url() {
+        let mut index = Index::new();
+        index.add(document("https://example.com/forms", "", &[], ""));
+        let results = index.search(&bubble(), "forms").unwrap();
+        assert_eq!(uris(&results), vec!["https://example.com/forms"]);
+    }
+
+    #[test]
+    fn test_add_replaces() {
+        let mut index = Index::new();
+        index.add(document("https://example.com/a", "Forms", &[], ""));
+        index.add(document("https://example.com/a", "Tables", &[], ""));
+        assert_eq!(index.len(), 1);
+        assert!(index.search(&bubble(), "forms").unwrap().is_empty());
+        assert_eq!(index.search(&bubble(), "tables").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut index = Index::new();
+        index.add(document("https://example.com/a", "Forms", &[], "body"));
+        assert!(index.remove(
+            &"https://example.com/a"
+                .parse::<IriAbsoluteString>()
+                .unwrap()
+        ));
+        assert!(index.is_empty());
+        assert!(index.postings.is_empty());
+        assert_eq!(index.total_lengths, [0; 4]);
+    }
+
+    #[test]
+    fn test_collect_garbage() {
+        let mut index = Index::new();
+        index.add(document("https://example.com/a", "A", &[], ""));
+        index.add(document("https://example.com/spam/b", "B", &[], ""));
+        index.add(document("https://another.com/c", "C", &[], ""));
+        let other = Bubble::parse_html(
+            r#"<html><head><title>Other</title></head><body>
+            <a class="bubble-search-page" href="https://another.com/c">C</a>
+        </body></html>"#,
+        )
+        .unwrap();
+
+        let (bubble, other) = (bubble(), other);
+        let removed = index
+            .collect_garbage(&[&Expansion::new(&bubble), &Expansion::new(&other)])
+            .unwrap();
+        assert_eq!(
+            removed.iter().map(|uri| uri.as_str()).collect::<Vec<_>>(),
+            vec!["https://example.com/spam/b"]
+        );
+        assert_eq!(index.len(), 2);
+        let spam = "https://example.com/spam/b"
+            .parse::<IriAbsoluteString>()
+            .unwrap();
+        assert!(index.is_tombstoned(&spam));
+        assert_eq!(index.tombstones().count(), 1);
+    }
+
+    #[test]
+    fn test_collect_garbage_applies_parent_excludes() {
+        let mut index = Index::new();
+        index.add(document("https://friend.org/ads", "Ads", &[], ""));
+        index.add(document("https://friend.org/docs", "Docs", &[], ""));
+        let root = Bubble::parse_html(
+            r#"<html><head><title>Root</title></head><body>
+            <a class="bubble-expand" href="https://friend.org/bubble">Friend</a>
+            <a class="bubble-exclude-page" href="https://friend.org/ads">Ads</a>
+        </body></html>"#,
+        )
+        .unwrap();
+        let friend = r#"<html><head><title>Friend</title></head><body>
+            <a class="bubble-search-site" href="https://friend.org">Friend</a>
+        </body></html>"#;
+        let expansion =
+            Expansion::resolve(&root, &Limits::default(), |_| Bubble::parse_html(friend));
+        let removed = index.collect_garbage(&[&expansion]).unwrap();
+        assert_eq!(
+            removed.iter().map(|uri| uri.as_str()).collect::<Vec<_>>(),
+            vec!["https://friend.org/ads"]
+        );
+    }
+
+    #[test]
+    fn test_collect_garbage_refuses_unresolved_expansion() {
+        let mut index = Index::new();
+        index.add(document("https://friend.org/docs", "Docs", &[], ""));
+        let root = Bubble::parse_html(
+            r#"<html><head><title>Root</title></head><body>
+            <a class="bubble-expand" href="https://friend.org/bubble">Friend</a>
+        </body></html>"#,
+        )
+        .unwrap();
+        let expansion = Expansion::resolve(&root, &Limits::default(), |_| {
+            Err::<Bubble, _>("unreachable")
+        });
+        assert_eq!(
+            index.collect_garbage(&[&expansion]),
+            Err(IndexError::IncompleteExpansion(
+                "https://friend.org/bubble".to_string()
+            ))
+        );
+        assert_eq!(index.len(), 1);
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let path = std::env::temp_dir().join(format!("bubble-index-{}.json", std::process::id()));
+        let mut index = Index::new();
+        index.add(document(
+            "https://example.com/a",
+            "Forms",
+            &[],
+            "html forms",
+        ));
+        index.save(&path).unwrap();
+        let loaded = Index::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(
+            loaded.search(&bubble(), r#""html forms""#).unwrap(),
+            index.search(&bubble(), r#""html forms""#).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_load_missing() {
+        let index = Index::load(Path::new("/nonexistent/bubble-index.json")).unwrap();
+        assert!(index.is_empty());
+    }
+}
@@ -0,0 +1,17 @@
+mod authority;
+mod duplicates;
+mod index;
+mod parse;
+mod query;
+mod ranking;
+mod snippet;
+mod tokenize;
+mod topics;
+
+pub use duplicates::{is_near_duplicate, simhash, MAX_DISTANCE};
+pub use index::{DocId, Index, IndexError, SearchResult};
+pub use parse::QueryParseError;
+pub use query::{Clause, DateRange, Filter, Query};
+pub use ranking::{Field, FieldWeights, Ranking};
+pub use snippet::{Fragment, Snippet, SNIPPET_WORDS};
+pub use tokenize::{tokenize, tokenize_with_offsets};
@@ -0,0 +1,494 @@
+use thiserror::Error;
+
+use crate::document::is_iso_date;
+
+use super::{
+    query::{Clause, DateRange, Filter, Query},
+    tokenize::tokenize,
+};
+
+/// Positions are counted in characters from the start of the query.
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum QueryParseError {
+    #[error("Unterminated phrase starting at position {0}")]
+    UnterminatedPhrase(usize),
+    #[error("Unmatched parenthesis at position {0}")]
+    UnmatchedParenthesis(usize),
+    #[error("Missing search terms at position {0}")]
+    MissingOperand(usize),
+    #[error("Missing value for filter {0} at position {1}")]
+    MissingFilterValue(String, usize),
+    #[error("Invalid date range {0} at position {1}, expected e.g. 2020-01..2021")]
+    InvalidDateRange(String, usize),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Word(String),
+    Phrase(String),
+    Filter(String, String),
+    Not,
+    And,
+    Or,
+    Open,
+    Close,
+}
+
+impl Query {
+    /// Parse a query. Terms are combined with AND unless joined by OR;
+    /// `-` or NOT excludes, `"..."` is a phrase and `name:value` a filter.
+    pub fn parse(query: &str) -> Result<Self, QueryParseError> {
+        let tokens = lex(query)?;
+        let mut parser = Parser {
+            tokens,
+            next: 0,
+            end: query.chars().count(),
+        };
+        let query = parser.parse_or()?;
+        if let Some((_, position)) = parser.peek() {
+            // the only thing that can stop a top level expression early
+            return Err(QueryParseError::UnmatchedParenthesis(*position));
+        }
+        Ok(query.unwrap_or(Query::And(Vec::new())))
+    }
+}
+
+fn lex(query: &str) -> Result<Vec<(Token, usize)>, QueryParseError> {
+    let chars = query.chars().collect::<Vec<_>>();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c == '(' {
+            tokens.push((Token::Open, i));
+            i += 1;
+        } else if c == ')' {
+            tokens.push((Token::Close, i));
+            i += 1;
+        } else if c == '"' {
+            let (phrase, next) = lex_phrase(&chars, i)?;
+            tokens.push((Token::Phrase(phrase), i));
+            i = next;
+        } else if c == '-' && chars.get(i + 1).is_some_and(|c| !c.is_whitespace()) {
+            tokens.push((Token::Not, i));
+            i += 1;
+        } else {
+            let start = i;
+            while i < chars.len() && !is_word_boundary(chars[i]) {
+                i += 1;
+            }
+            let word = chars[start..i].iter().collect::<String>();
+            let token = match word.as_str() {
+                "AND" => Token::And,
+                "OR" => Token::Or,
+                "NOT" => Token::Not,
+                _ => match filter_name(&word) {
+                    Some(name) => {
+                        let mut value = word[name.len() + 1..].to_string();
+                        if value.is_empty() && chars.get(i) == Some(&'"') {
+                            let (phrase, next) = lex_phrase(&chars, i)?;
+                            value = phrase;
+                            i = next;
+                        }
+                        if value.trim().is_empty() {
+                            return Err(QueryParseError::MissingFilterValue(
+                                name.to_string(),
+                                start,
+                            ));
+                        }
+                        Token::Filter(name.to_string(), value)
+                    }
+                    None => Token::Word(word),
+                },
+            };
+            tokens.push((token, start));
+        }
+    }
+    Ok(tokens)
+}
+
+fn lex_phrase(chars: &[char], start: usize) -> Result<(String, usize), QueryParseError> {
+    let end = chars[start + 1..]
+        .iter()
+        .position(|c| *c == '"')
+        .ok_or(QueryParseError::UnterminatedPhrase(start))?
+        + start
+        + 1;
+    Ok((chars[start + 1..end].iter().collect(), end + 1))
+}
+
+fn is_word_boundary(c: char) -> bool {
+    c.is_whitespace() || c == '(' || c == ')' || c == '"'
+}
+
+const FILTERS: &[&str] = &[
+    "site",
+    "indexable",
+    "section",
+    "tag",
+    "bubble",
+    "topic",
+    "lang",
+    "date",
+];
+
+// `name:` starts a filter if it's one we know, unless it's the scheme of a
+// URL; anything else, like `std::vec::Vec` or `error:E0277`, is text
+fn filter_name(word: &str) -> Option<&str> {
+    let (name, rest) = word.split_once(':')?;
+    (FILTERS.contains(&name) && !rest.starts_with("//")).then_some(name)
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    next: usize,
+    end: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&(Token, usize)> {
+        self.tokens.get(self.next)
+    }
+
+    fn position(&self) -> usize {
+        self.peek().map_or(self.end, |(_, position)| *position)
+    }
+
+    fn advance(&mut self) -> Option<(Token, usize)> {
+        let token = self.tokens.get(self.next).cloned();
+        self.next += 1;
+        token
+    }
+
+    // or := and ("OR" and)*
+    fn parse_or(&mut self) -> Result<Option<Query>, QueryParseError> {
+        let mut alternatives = Vec::new();
+        alternatives.extend(self.parse_and()?);
+        while let Some((Token::Or, position)) = self.peek() {
+            if alternatives.is_empty() {
+                return Err(QueryParseError::MissingOperand(*position));
+            }
+            self.advance();
+            let operand = self.position();
+            match self.parse_and()? {
+                Some(query) => alternatives.push(query),
+                None => return Err(QueryParseError::MissingOperand(operand)),
+            }
+        }
+        Ok(match alternatives.len() {
+            0 => None,
+            1 => alternatives.pop(),
+            _ => Some(Query::Or(alternatives)),
+        })
+    }
+
+    // and := unary (["AND"] unary)*
+    fn parse_and(&mut self) -> Result<Option<Query>, QueryParseError> {
+        let mut conjuncts = Vec::new();
+        loop {
+            match self.peek() {
+                None | Some((Token::Or | Token::Close, _)) => break,
+                Some((Token::And, position)) => {
+                    let position = *position;
+                    self.advance();
+                    if conjuncts.is_empty() || !self.starts_unary() {
+                        return Err(QueryParseError::MissingOperand(if conjuncts.is_empty() {
+                            position
+                        } else {
+                            self.position()
+                        }));
+                    }
+                }
+                Some(_) => conjuncts.extend(self.parse_unary()?),
+            }
+        }
+        Ok(match conjuncts.len() {
+            0 => None,
+            1 => conjuncts.pop(),
+            _ => Some(Query::And(conjuncts)),
+        })
+    }
+
+    fn starts_unary(&self) -> bool {
+        !matches!(
+            self.peek(),
+            None | Some((Token::And | Token::Or | Token::Close, _))
+        )
+    }
+
+    // unary := ("-" | "NOT") unary | "(" or ")" | phrase | filter | word
+    fn parse_unary(&mut self) -> Result<Option<Query>, QueryParseError> {
+        let (token, position) = self.advance().expect("caller checked for a token");
+        match token {
+            Token::Not => {
+                if !self.starts_unary() {
+                    return Err(QueryParseError::MissingOperand(self.position()));
+                }
+                Ok(self.parse_unary()?.map(|query| Query::Not(Box::new(query))))
+            }
+            Token::Open => {
+                let query = self.parse_or()?;
+                match self.advance() {
+                    Some((Token::Close, _)) => Ok(query),
+                    _ => Err(QueryParseError::UnmatchedParenthesis(position)),
+                }
+            }
+            Token::Word(text) | Token::Phrase(text) => Ok(text_query(&text)),
+            Token::Filter(name, value) => {
+                Ok(Some(Query::Filter(parse_filter(&name, &value, position)?)))
+            }
+            Token::And | Token::Or | Token::Close => {
+                unreachable!("starts_unary excludes operators")
+            }
+        }
+    }
+}
+
+// a single term, or a phrase when the text holds several
+fn text_query(text: &str) -> Option<Query> {
+    let mut terms = tokenize(text);
+    match terms.len() {
+        0 => None,
+        1 => Some(Query::Clause(Clause::Term(terms.remove(0)))),
+        _ => Some(Query::Clause(Clause::Phrase(terms))),
+    }
+}
+
+fn parse_filter(name: &str, value: &str, position: usize) -> Result<Filter, QueryParseError> {
+    let value = value.trim();
+    Ok(match name {
+        "site" => Filter::Site(value.trim_end_matches('/').to_ascii_lowercase()),
+        "indexable" => Filter::Indexable(value.to_string()),
+        "section" => Filter::Section(value.to_string()),
+        "tag" => Filter::Tag(value.to_string()),
+        "bubble" => Filter::Bubble(value.to_string()),
+        "topic" => Filter::Topic(value.to_string()),
+        "lang" => Filter::Lang(value.to_ascii_lowercase()),
+        "date" => Filter::Date(
+            parse_date_range(value)
+                .ok_or_else(|| QueryParseError::InvalidDateRange(value.to_string(), position))?,
+        ),
+        _ => unreachable!("filter_name only lets known filters through"),
+    })
+}
+
+// 2020, 2020-01..2021, 2020.. or ..2021-06-30
+fn parse_date_range(value: &str) -> Option<DateRange> {
+    let (from, to) = match value.split_once("..") {
+        Some((from, to)) => (from, to),
+        None => (value, value),
+    };
+    let bound = |bound: &str| -> Option<Option<String>> {
+        if bound.is_empty() {
+            Some(None)
+        } else {
+            is_iso_date(bound).then(|| Some(bound.to_string()))
+        }
+    };
+    let (from, to) = (bound(from)?, bound(to)?);
+    if from.is_none() && to.is_none() {
+        return None;
+    }
+    Some(DateRange::new(from, to))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn term(term: &str) -> Query {
+        Query::Clause(Clause::Term(term.to_string()))
+    }
+
+    fn phrase(terms: &[&str]) -> Query {
+        Query::Clause(Clause::Phrase(
+            terms.iter().map(|term| term.to_string()).collect(),
+        ))
+    }
+
+    #[test]
+    fn test_parse_terms_are_and() {
+        assert_eq!(
+            Query::parse("HTML forms"),
+            Ok(Query::And(vec![term("html"), term("forms")]))
+        );
+        assert_eq!(
+            Query::parse("html AND forms"),
+            Ok(Query::And(vec![term("html"), term("forms")]))
+        );
+    }
+
+    #[test]
+    fn test_parse_phrase() {
+        assert_eq!(
+            Query::parse(r#"submit "html forms""#),
+            Ok(Query::And(vec![term("submit"), phrase(&["html", "forms"])]))
+        );
+        assert_eq!(Query::parse(r#""forms""#), Ok(term("forms")));
+    }
+
+    #[test]
+    fn test_parse_word_with_punctuation_is_phrase() {
+        assert_eq!(Query::parse("e-mail"), Ok(phrase(&["e", "mail"])));
+    }
+
+    #[test]
+    fn test_parse_or_binds_looser_than_and() {
+        assert_eq!(
+            Query::parse("html forms OR tables"),
+            Ok(Query::Or(vec![
+                Query::And(vec![term("html"), term("forms")]),
+                term("tables")
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_parse_parentheses() {
+        assert_eq!(
+            Query::parse("html (forms OR tables)"),
+            Ok(Query::And(vec![
+                term("html"),
+                Query::Or(vec![term("forms"), term("tables")])
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_parse_exclusion() {
+        assert_eq!(
+            Query::parse("forms -spam NOT \"buy now\""),
+            Ok(Query::And(vec![
+                term("forms"),
+                Query::Not(Box::new(term("spam"))),
+                Query::Not(Box::new(phrase(&["buy", "now"]))),
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_parse_filters() {
+        assert_eq!(
+            Query::parse(
+                r#"forms site:Example.com indexable:"MDN docs" section:Forums tag:beginners bubble:Rust topic:Q8811 lang:EN"#
+            ),
+            Ok(Query::And(vec![
+                term("forms"),
+                Query::Filter(Filter::Site("example.com".to_string())),
+                Query::Filter(Filter::Indexable("MDN docs".to_string())),
+                Query::Filter(Filter::Section("Forums".to_string())),
+                Query::Filter(Filter::Tag("beginners".to_string())),
+                Query::Filter(Filter::Bubble("Rust".to_string())),
+                Query::Filter(Filter::Topic("Q8811".to_string())),
+                Query::Filter(Filter::Lang("en".to_string())),
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_parse_date_ranges() {
+        let date = |from: Option<&str>, to: Option<&str>| {
+            Query::Filter(Filter::Date(DateRange::new(
+                from.map(str::to_string),
+                to.map(str::to_string),
+            )))
+        };
+        assert_eq!(
+            Query::parse("date:2020-01..2021"),
+            Ok(date(Some("2020-01"), Some("2021")))
+        );
+        assert_eq!(Query::parse("date:2020.."), Ok(date(Some("2020"), None)));
+        assert_eq!(
+            Query::parse("date:..2021-06-30"),
+            Ok(date(None, Some("2021-06-30")))
+        );
+        assert_eq!(
+            Query::parse("date:2020"),
+            Ok(date(Some("2020"), Some("2020")))
+        );
+    }
+
+    #[test]
+    fn test_parse_url_is_not_filter() {
+        assert_eq!(
+            Query::parse("https://example.com"),
+            Ok(phrase(&["https", "example", "com"]))
+        );
+    }
+
+    #[test]
+    fn test_parse_unknown_filter_is_text() {
+        assert_eq!(
+            Query::parse("forms colour:red"),
+            Ok(Query::And(vec![term("forms"), phrase(&["colour", "red"])]))
+        );
+        assert_eq!(
+            Query::parse("std::vec::Vec"),
+            Ok(phrase(&["std", "vec", "vec"]))
+        );
+        assert_eq!(Query::parse("error:E0277"), Ok(phrase(&["error", "e0277"])));
+        assert_eq!(
+            Query::parse("note: foo"),
+            Ok(Query::And(vec![term("note"), term("foo")]))
+        );
+    }
+
+    #[test]
+    fn test_parse_empty() {
+        assert_eq!(Query::parse("  "), Ok(Query::And(vec![])));
+        assert!(Query::parse("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            Query::parse(r#"forms "html"#),
+            Err(QueryParseError::UnterminatedPhrase(6))
+        );
+        assert_eq!(
+            Query::parse("(forms OR html"),
+            Err(QueryParseError::UnmatchedParenthesis(0))
+        );
+        assert_eq!(
+            Query::parse("forms) html"),
+            Err(QueryParseError::UnmatchedParenthesis(5))
+        );
+        assert_eq!(
+            Query::parse("forms OR"),
+            Err(QueryParseError::MissingOperand(8))
+        );
+        assert_eq!(
+            Query::parse("OR forms"),
+            Err(QueryParseError::MissingOperand(0))
+        );
+        assert_eq!(
+            Query::parse("forms AND"),
+            Err(QueryParseError::MissingOperand(9))
+        );
+        assert_eq!(
+            Query::parse("forms NOT"),
+            Err(QueryParseError::MissingOperand(9))
+        );
+        assert_eq!(
+            Query::parse("forms site:"),
+            Err(QueryParseError::MissingFilterValue("site".to_string(), 6))
+        );
+        assert_eq!(
+            Query::parse("date:last-week"),
+            Err(QueryParseError::InvalidDateRange(
+                "last-week".to_string(),
+                0
+            ))
+        );
+    }
+
+    #[test]
+    fn test_parse_error_positions_count_characters() {
+        assert_eq!(
+            Query::parse(r#"über "html"#),
+            Err(QueryParseError::UnterminatedPhrase(5))
+        );
+    }
+}
@@ -0,0 +1,279 @@
+use crate::{bubble::Admission, document::Document, topic::Qid};
+
+/// A parsed search query.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Query {
+    Clause(Clause),
+    Filter(Filter),
+    And(Vec<Query>),
+    Or(Vec<Query>),
+    Not(Box<Query>),
+}
+
+/// The parts of a query that match text, and so contribute to the score.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Clause {
+    Term(String),
+    Phrase(Vec<String>),
+}
+
+/// Restrictions on which documents match, expressed in terms of the bubble.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Filter {
+    // the document's host, or a subdomain of it
+    Site(String),
+    // one of the indexables admitting the document has this label
+    Indexable(String),
+    // one of the indexables admitting the document is under this heading
+    Section(String),
+    // one of the indexables admitting the document has this tag
+    Tag(String),
+    // the document was admitted by a bubble with this title
+    Bubble(String),
+    // a bubble admitting the document is about this topic, given as a QID,
+    // URL or label
+    Topic(String),
+    // the document's language, or a regional variant of it
+    Lang(String),
+    Date(DateRange),
+}
+
+/// An inclusive range of ISO 8601 dates. Bounds may be partial, so `2024`
+/// up to `2024` covers the whole year.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DateRange {
+    pub(super) from: Option<String>,
+    pub(super) to: Option<String>,
+}
+
+/// What filters get to look at when deciding whether a document matches.
+pub(super) struct Candidate<'a> {
+    pub(super) document: &'a Document,
+    pub(super) admissions: Vec<Admission<'a>>,
+}
+
+impl Query {
+    /// The clauses that count towards a document's score; clauses under a
+    /// negation only exclude.
+    pub fn scoring_clauses(&self) -> Vec<&Clause> {
+        match self {
+            Query::Clause(clause) => vec![clause],
+            Query::Filter(_) | Query::Not(_) => Vec::new(),
+            Query::And(queries) | Query::Or(queries) => {
+                queries.iter().flat_map(Query::scoring_clauses).collect()
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        matches!(self, Query::And(queries) if queries.is_empty())
+    }
+}
+
+impl Filter {
+    pub(super) fn matches(&self, candidate: &Candidate) -> bool {
+        match self {
+            Filter::Site(site) => candidate
+                .document
+                .uri()
+                .authority_components()
+                .map(|authority| authority.host().to_ascii_lowercase())
+                .is_some_and(|host| {
+                    host == *site
+                        || host
+                            .strip_suffix(site.as_str())
+                            .is_some_and(|prefix| prefix.ends_with('.'))
+                }),
+            Filter::Indexable(label) => candidate
+                .admissions
+                .iter()
+                .flat_map(|admission| admission.indexables())
+                .any(|indexable| indexable.label().trim().eq_ignore_ascii_case(label)),
+            Filter::Section(section) => candidate
+                .admissions
+                .iter()
+                .flat_map(|admission| admission.indexables())
+                .filter_map(|indexable| indexable.section())
+                .any(|heading| heading.eq_ignore_ascii_case(section)),
+            Filter::Tag(tag) => candidate
+                .admissions
+                .iter()
+                .flat_map(|admission| admission.indexables())
+                .any(|indexable| indexable.has_tag(tag)),
+            Filter::Bubble(title) => candidate.admissions.iter().any(|admission| {
+                admission
+                    .bubble()
+                    .title()
+                    .trim()
+                    .eq_ignore_ascii_case(title)
+            }),
+            Filter::Topic(topic) => {
+                // QIDs are compared parsed, so q42 and Q42 are the same item
+                let qid = topic.parse::<Qid>().ok();
+                candidate
+                    .admissions
+                    .iter()
+                    .flat_map(|admission| admission.bubble().topic().references())
+                    .any(|reference| {
+                        reference.uri().as_str() == topic
+                            || qid.is_some_and(|qid| reference.qid() == Some(qid))
+                            || reference
+                                .labels()
+                                .iter()
+                                .any(|label| label.text().trim().eq_ignore_ascii_case(topic))
+                    })
+            }
+            Filter::Lang(lang) => candidate.document.lang().is_some_and(|document_lang| {
+                let document_lang = document_lang.to_ascii_lowercase();
+                document_lang == *lang
+                    || document_lang
+                        .strip_prefix(lang.as_str())
+                        .is_some_and(|region| region.starts_with('-'))
+            }),
+            Filter::Date(range) => candidate
+                .document
+                .date()
+                .is_some_and(|date| range.contains(date)),
+        }
+    }
+}
+
+impl DateRange {
+    pub fn new(from: Option<String>, to: Option<String>) -> Self {
+        Self { from, to }
+    }
+
+    pub fn contains(&self, date: &str) -> bool {
+        let after_start = self.from.as_deref().is_none_or(|from| date >= from);
+        // compare at the precision of the bound, so a day in 2024 is before
+        // the end of 2024. Dates come from pages and stored indexes, so they
+        // may not be ASCII; those are compared whole rather than cut mid char
+        let before_end = self
+            .to
+            .as_deref()
+            .is_none_or(|to| date.get(..date.len().min(to.len())).unwrap_or(date) <= to);
+        after_start && before_end
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bubble::{Bubble, Expansion};
+
+    use super::*;
+
+    fn bubble() -> Bubble {
+        Bubble::parse_html(
+            r#"<html>
+            <head><title>Web</title></head>
+            <body>
+                <a class="bubble-wikidata-topic" href="https://www.wikidata.org/wiki/Q8811">HTML</a>
+                <h2>Docs</h2>
+                <a class="bubble-search-site" href="https://www.example.com" data-tags="reference">Example docs</a>
+            </body>
+        </html>"#,
+        )
+        .unwrap()
+    }
+
+    fn document() -> Document {
+        Document::new(
+            "https://www.example.com/a".parse().unwrap(),
+            "A".to_string(),
+            vec![],
+            "".to_string(),
+        )
+        .with_lang("en-GB")
+        .with_date("2023-04-05")
+    }
+
+    fn matches(filter: Filter) -> bool {
+        let bubble = bubble();
+        let document = document();
+        let expansion = Expansion::new(&bubble);
+        filter.matches(&Candidate {
+            document: &document,
+            admissions: expansion.admitting(document.uri()),
+        })
+    }
+
+    #[test]
+    fn test_site_filter() {
+        assert!(matches(Filter::Site("www.example.com".to_string())));
+        assert!(matches(Filter::Site("example.com".to_string())));
+        assert!(!matches(Filter::Site("ample.com".to_string())));
+    }
+
+    #[test]
+    fn test_indexable_filter() {
+        assert!(matches(Filter::Indexable("example docs".to_string())));
+        assert!(!matches(Filter::Indexable("example".to_string())));
+    }
+
+    #[test]
+    fn test_section_filter() {
+        assert!(matches(Filter::Section("docs".to_string())));
+        assert!(!matches(Filter::Section("forums".to_string())));
+    }
+
+    #[test]
+    fn test_tag_filter() {
+        assert!(matches(Filter::Tag("Reference".to_string())));
+        assert!(!matches(Filter::Tag("beginners".to_string())));
+    }
+
+    #[test]
+    fn test_bubble_filter() {
+        assert!(matches(Filter::Bubble("web".to_string())));
+        assert!(!matches(Filter::Bubble("rust".to_string())));
+    }
+
+    #[test]
+    fn test_topic_filter() {
+        assert!(matches(Filter::Topic("Q8811".to_string())));
+        assert!(matches(Filter::Topic("q8811".to_string())));
+        assert!(matches(Filter::Topic(
+            "https://www.wikidata.org/wiki/Q8811".to_string()
+        )));
+        assert!(matches(Filter::Topic("html".to_string())));
+        assert!(!matches(Filter::Topic("Q42".to_string())));
+    }
+
+    #[test]
+    fn test_lang_filter() {
+        assert!(matches(Filter::Lang("en".to_string())));
+        assert!(matches(Filter::Lang("en-gb".to_string())));
+        assert!(!matches(Filter::Lang("e".to_string())));
+        assert!(!matches(Filter::Lang("nl".to_string())));
+    }
+
+    #[test]
+    fn test_date_range() {
+        let range = |from: Option<&str>, to: Option<&str>| {
+            DateRange::new(from.map(str::to_string), to.map(str::to_string))
+        };
+        assert!(range(Some("2023"), Some("2023")).contains("2023-04-05"));
+        assert!(range(Some("2023-04-05"), None).contains("2023-04-05"));
+        assert!(range(None, Some("2023-04")).contains("2023-04-05"));
+        assert!(!range(Some("2023-05"), None).contains("2023-04-05"));
+        assert!(!range(None, Some("2022")).contains("2023-04-05"));
+        // not a date, but it mustn't panic either
+        assert!(!range(None, Some("2023-04")).contains("2023-0é"));
+    }
+
+    #[test]
+    fn test_scoring_clauses_skip_negation() {
+        let query = Query::And(vec![
+            Query::Clause(Clause::Term("forms".to_string())),
+            Query::Not(Box::new(Query::Clause(Clause::Term("spam".to_string())))),
+            Query::Or(vec![Query::Clause(Clause::Term("html".to_string()))]),
+        ]);
+        assert_eq!(
+            query.scoring_clauses(),
+            vec![
+                &Clause::Term("forms".to_string()),
+                &Clause::Term("html".to_string())
+            ]
+        );
+    }
+}
@@ -0,0 +1,173 @@
+use serde::{Deserialize, Serialize};
+
+use crate::bubble::Reach;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Field {
+    Title,
+    Headings,
+    Body,
+    Url,
+}
+
+impl Field {
+    pub const ALL: [Field; 4] = [Field::Title, Field::Headings, Field::Body, Field::Url];
+
+    pub(super) fn slot(self) -> usize {
+        self as usize
+    }
+}
+
+/// How much a match in each field counts compared to a match in the body.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldWeights {
+    pub title: f64,
+    pub headings: f64,
+    pub body: f64,
+    pub url: f64,
+}
+
+impl FieldWeights {
+    pub fn get(&self, field: Field) -> f64 {
+        match field {
+            Field::Title => self.title,
+            Field::Headings => self.headings,
+            Field::Body => self.body,
+            Field::Url => self.url,
+        }
+    }
+}
+
+impl Default for FieldWeights {
+    fn default() -> Self {
+        Self {
+            title: 3.0,
+            headings: 2.0,
+            body: 1.0,
+            url: 1.5,
+        }
+    }
+}
+
+/// BM25F parameters: field frequencies are length-normalized and weighted
+/// per field before BM25 saturation is applied to their sum.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ranking {
+    pub k1: f64,
+    pub b: f64,
+    pub weights: FieldWeights,
+    // how strongly link authority within the bubble scales the text score;
+    // 0 ignores links altogether
+    pub authority: f64,
+    // what each `bubble-expand` step away from the searched bubble leaves of
+    // a document's score; 1 trusts expanded bubbles as much as our own
+    pub expansion_decay: f64,
+    // how much more a document counts when it mentions one of the searched
+    // bubble's topics; 0 ignores topics
+    pub topic: f64,
+}
+
+impl Default for Ranking {
+    fn default() -> Self {
+        Self {
+            k1: 1.2,
+            b: 0.75,
+            weights: FieldWeights::default(),
+            authority: 0.3,
+            expansion_decay: 0.5,
+            topic: 0.5,
+        }
+    }
+}
+
+impl Ranking {
+    pub(super) fn idf(&self, documents: usize, matching: usize) -> f64 {
+        let n = documents as f64;
+        let df = matching as f64;
+        ((n - df + 0.5) / (df + 0.5) + 1.0).ln()
+    }
+
+    /// Combine per-field frequencies into a BM25F score for a single clause.
+    pub(super) fn score(
+        &self,
+        idf: f64,
+        frequencies: &[u32; 4],
+        lengths: &[u32; 4],
+        average_lengths: &[f64; 4],
+    ) -> f64 {
+        let tf: f64 = Field::ALL
+            .iter()
+            .map(|field| {
+                let slot = field.slot();
+                if frequencies[slot] == 0 || average_lengths[slot] == 0.0 {
+                    return 0.0;
+                }
+                let normalization =
+                    1.0 - self.b + self.b * lengths[slot] as f64 / average_lengths[slot];
+                self.weights.get(*field) * frequencies[slot] as f64 / normalization
+            })
+            .sum();
+        idf * tf / (self.k1 + tf)
+    }
+
+    /// The factor a document's score is multiplied by, given its authority
+    /// relative to the average document.
+    pub(super) fn boost(&self, authority: f64) -> f64 {
+        authority.powf(self.authority)
+    }
+
+    /// The factor a document's score is multiplied by, given how strongly
+    /// it mentions the bubble's topics, from 0 to 1.
+    pub(super) fn topical(&self, mention: f64) -> f64 {
+        1.0 + self.topic * mention
+    }
+
+    /// How much documents from a bubble count, given how it was reached.
+    pub(super) fn trust(&self, reach: &Reach) -> f64 {
+        reach.weight() * self.expansion_decay.powi(reach.depth() as i32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_idf_favours_rare_terms() {
+        let ranking = Ranking::default();
+        assert!(ranking.idf(100, 1) > ranking.idf(100, 50));
+        assert!(ranking.idf(100, 100) > 0.0);
+    }
+
+    #[test]
+    fn test_title_outweighs_body() {
+        let ranking = Ranking::default();
+        let lengths = [10, 10, 10, 10];
+        let averages = [10.0, 10.0, 10.0, 10.0];
+        let title = ranking.score(1.0, &[1, 0, 0, 0], &lengths, &averages);
+        let body = ranking.score(1.0, &[0, 0, 1, 0], &lengths, &averages);
+        assert!(title > body);
+    }
+
+    #[test]
+    fn test_average_authority_is_neutral() {
+        let ranking = Ranking::default();
+        assert_eq!(ranking.boost(1.0), 1.0);
+        assert!(ranking.boost(4.0) > 1.0);
+        assert!(ranking.boost(0.5) < 1.0);
+        let ranking = Ranking {
+            authority: 0.0,
+            ..Ranking::default()
+        };
+        assert_eq!(ranking.boost(4.0), 1.0);
+    }
+
+    #[test]
+    fn test_longer_fields_score_lower() {
+        let ranking = Ranking::default();
+        let averages = [10.0, 10.0, 10.0, 10.0];
+        let short = ranking.score(1.0, &[0, 0, 1, 0], &[0, 0, 5, 0], &averages);
+        let long = ranking.score(1.0, &[0, 0, 1, 0], &[0, 0, 50, 0], &averages);
+        assert!(short > long);
+    }
+}
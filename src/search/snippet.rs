@@ -0,0 +1,215 @@
+use std::collections::HashSet;
+
+use super::tokenize::tokenize_with_offsets;
+
+/// How many words of context a snippet shows by default.
+pub const SNIPPET_WORDS: usize = 30;
+
+// words shown before the first match, so it doesn't open the snippet
+const LEADING_WORDS: usize = 5;
+
+const ELLIPSIS: &str = "…";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Fragment {
+    Text(String),
+    Highlight(String),
+}
+
+/// A passage of a document with the query terms it contains highlighted.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Snippet {
+    fragments: Vec<Fragment>,
+}
+
+impl Snippet {
+    /// Pick the passage of `words` words that contains the most distinct
+    /// query terms, then the most matches, then the closest together ones.
+    pub fn new(text: &str, terms: &HashSet<String>, words: usize) -> Self {
+        let tokens = tokenize_with_offsets(text);
+        if tokens.is_empty() || words == 0 {
+            return Self::default();
+        }
+        let matches = tokens
+            .iter()
+            .enumerate()
+            .filter(|(_, (_, term))| terms.contains(term))
+            .map(|(i, _)| i)
+            .collect::<Vec<_>>();
+
+        let last_start = tokens.len().saturating_sub(words);
+        let start = matches
+            .iter()
+            .map(|m| {
+                m.saturating_sub(LEADING_WORDS.min(words / 2))
+                    .min(last_start)
+            })
+            .max_by_key(|start| {
+                let in_window = matches
+                    .iter()
+                    .filter(|m| (*start..start + words).contains(*m))
+                    .collect::<Vec<_>>();
+                let distinct = in_window
+                    .iter()
+                    .map(|m| &tokens[**m].1)
+                    .collect::<HashSet<_>>()
+                    .len();
+                let span = *in_window[in_window.len() - 1] - *in_window[0];
+                // prefer earlier windows on ties
+                (
+                    distinct,
+                    in_window.len(),
+                    usize::MAX - span,
+                    usize::MAX - start,
+                )
+            })
+            .unwrap_or(0);
+        let end = (start + words).min(tokens.len());
+
+        let mut snippet = Self::default();
+        if start > 0 {
+            snippet.push_text(ELLIPSIS);
+        }
+        // at the edges of the text keep punctuation that isn't part of a term
+        let mut offset = if start == 0 { 0 } else { tokens[start].0.start };
+        for (range, term) in &tokens[start..end] {
+            snippet.push_text(&text[offset..range.start]);
+            if terms.contains(term) {
+                snippet
+                    .fragments
+                    .push(Fragment::Highlight(text[range.clone()].to_string()));
+            } else {
+                snippet.push_text(&text[range.clone()]);
+            }
+            offset = range.end;
+        }
+        if end < tokens.len() {
+            snippet.push_text(ELLIPSIS);
+        } else {
+            snippet.push_text(&text[offset..]);
+        }
+        snippet
+    }
+
+    fn push_text(&mut self, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+        match self.fragments.last_mut() {
+            Some(Fragment::Text(previous)) => previous.push_str(text),
+            _ => self.fragments.push(Fragment::Text(text.to_string())),
+        }
+    }
+
+    pub fn fragments(&self) -> &[Fragment] {
+        &self.fragments
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fragments.is_empty()
+    }
+
+    /// The snippet as plain text, without highlighting.
+    pub fn to_text(&self) -> String {
+        self.to_marked("", "")
+    }
+
+    /// The snippet as plain text, with highlights between the given markers.
+    pub fn to_marked(&self, open: &str, close: &str) -> String {
+        self.fragments
+            .iter()
+            .map(|fragment| match fragment {
+                Fragment::Text(text) => text.clone(),
+                Fragment::Highlight(text) => format!("{open}{text}{close}"),
+            })
+            .collect()
+    }
+
+    /// The snippet as escaped HTML, with highlights in `<mark>` elements.
+    pub fn to_html(&self) -> String {
+        self.fragments
+            .iter()
+            .map(|fragment| match fragment {
+                Fragment::Text(text) => escape_html(text),
+                Fragment::Highlight(text) => format!("<mark>{}</mark>", escape_html(text)),
+            })
+            .collect()
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn terms(terms: &[&str]) -> HashSet<String> {
+        terms.iter().map(|term| term.to_string()).collect()
+    }
+
+    #[test]
+    fn test_snippet_highlights() {
+        let snippet = Snippet::new("HTML Forms collect input.", &terms(&["forms"]), 30);
+        assert_eq!(
+            snippet.fragments(),
+            &[
+                Fragment::Text("HTML ".to_string()),
+                Fragment::Highlight("Forms".to_string()),
+                Fragment::Text(" collect input.".to_string()),
+            ]
+        );
+        assert_eq!(snippet.to_marked("[", "]"), "HTML [Forms] collect input.");
+        assert_eq!(snippet.to_text(), "HTML Forms collect input.");
+    }
+
+    #[test]
+    fn test_snippet_without_matches_starts_at_beginning() {
+        let snippet = Snippet::new("one two three four", &terms(&["five"]), 2);
+        assert_eq!(snippet.to_text(), "one two…");
+    }
+
+    #[test]
+    fn test_snippet_prefers_dense_passage() {
+        let text = "forms are mentioned here once. Then a lot of filler words follow \
+                    until html forms and html input appear together.";
+        let snippet = Snippet::new(text, &terms(&["html", "forms", "input"]), 6);
+        assert_eq!(
+            snippet.to_marked("[", "]"),
+            "…[html] [forms] and [html] [input] appear…"
+        );
+    }
+
+    #[test]
+    fn test_snippet_prefers_distinct_terms() {
+        let text = "forms forms forms a b c d e f g h i j k forms html";
+        let snippet = Snippet::new(text, &terms(&["html", "forms"]), 3);
+        assert_eq!(snippet.to_marked("[", "]"), "…k [forms] [html]");
+    }
+
+    #[test]
+    fn test_snippet_html_is_escaped() {
+        let snippet = Snippet::new("<b>forms</b> & \"inputs\"", &terms(&["forms"]), 30);
+        assert_eq!(
+            snippet.to_html(),
+            "&lt;b&gt;<mark>forms</mark>&lt;/b&gt; &amp; &quot;inputs&quot;"
+        );
+    }
+
+    #[test]
+    fn test_snippet_empty_text() {
+        assert!(Snippet::new("", &terms(&["forms"]), 30).is_empty());
+    }
+}
@@ -0,0 +1,64 @@
+use std::ops::Range;
+
+/// Split text into lowercase terms. Anything that isn't alphanumeric separates
+/// terms, which also works reasonably for URLs.
+pub fn tokenize(text: &str) -> Vec<String> {
+    tokenize_with_offsets(text)
+        .into_iter()
+        .map(|(_, term)| term)
+        .collect()
+}
+
+/// Like [`tokenize`], but also returns the byte range each term came from.
+pub fn tokenize_with_offsets(text: &str) -> Vec<(Range<usize>, String)> {
+    let mut terms = Vec::new();
+    let mut start = None;
+    for (offset, c) in text.char_indices().chain([(text.len(), ' ')]) {
+        match (start, c.is_alphanumeric()) {
+            (None, true) => start = Some(offset),
+            (Some(begin), false) => {
+                terms.push((begin..offset, text[begin..offset].to_lowercase()));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    terms
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokenize() {
+        assert_eq!(
+            tokenize("HTML forms: <input> & <button>!"),
+            vec!["html", "forms", "input", "button"]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_url() {
+        assert_eq!(
+            tokenize("https://example.com/Docs/forms"),
+            vec!["https", "example", "com", "docs", "forms"]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_with_offsets() {
+        assert_eq!(
+            tokenize_with_offsets("Über, forms"),
+            vec![(0..5, "über".to_string()), (7..12, "forms".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_unicode() {
+        assert_eq!(
+            tokenize("Ärger über Straßen"),
+            vec!["ärger", "über", "straßen"]
+        );
+    }
+}
@@ -0,0 +1,150 @@
+use crate::topic::{Entity, Label, Topic, Wikidata};
+
+use super::{
+    query::{Clause, Query},
+    tokenize::tokenize,
+};
+
+// one topic's names, each as the terms it tokenizes to
+pub(super) type Names = Vec<Vec<String>>;
+
+/// The names of each of a bubble's topics: the labels of its links in every
+/// language and, given a Wikidata dump, the item's labels and aliases.
+pub(super) fn topic_names(topic: &Topic, wikidata: Option<&Wikidata>) -> Vec<Names> {
+    topic
+        .references()
+        .iter()
+        .map(|reference| {
+            let labels = reference.labels().iter().map(Label::text);
+            let entity = wikidata.and_then(|wikidata| wikidata.resolve(reference));
+            let mut names = Names::new();
+            for name in labels.chain(entity.into_iter().flat_map(Entity::names)) {
+                let terms = tokenize(name);
+                if !terms.is_empty() && !names.contains(&terms) {
+                    names.push(terms);
+                }
+            }
+            names
+        })
+        .filter(|names| !names.is_empty())
+        .collect()
+}
+
+/// Let a term or phrase naming one of the topics match any of its names,
+/// so "WWW" also finds "World Wide Web". Unquoted words are matched one by
+/// one, so only single word names expand from them.
+pub(super) fn expand_query(query: &Query, topics: &[Names]) -> Query {
+    let expand_all = |queries: &[Query]| {
+        queries
+            .iter()
+            .map(|query| expand_query(query, topics))
+            .collect()
+    };
+    match query {
+        Query::Clause(clause) => {
+            let terms = match clause {
+                Clause::Term(term) => std::slice::from_ref(term),
+                Clause::Phrase(terms) => terms.as_slice(),
+            };
+            match topics
+                .iter()
+                .find(|names| names.iter().any(|name| name == terms))
+            {
+                // the name as given first, then the others
+                Some(names) if names.len() > 1 => Query::Or(
+                    std::iter::once(clause.clone())
+                        .chain(
+                            names
+                                .iter()
+                                .filter(|name| name.as_slice() != terms)
+                                .map(|name| name_clause(name)),
+                        )
+                        .map(Query::Clause)
+                        .collect(),
+                ),
+                _ => query.clone(),
+            }
+        }
+        Query::Filter(_) => query.clone(),
+        Query::And(queries) => Query::And(expand_all(queries)),
+        Query::Or(queries) => Query::Or(expand_all(queries)),
+        Query::Not(query) => Query::Not(Box::new(expand_query(query, topics))),
+    }
+}
+
+pub(super) fn name_clause(name: &[String]) -> Clause {
+    match name {
+        [term] => Clause::Term(term.clone()),
+        terms => Clause::Phrase(terms.to_vec()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bubble::Bubble;
+
+    use super::*;
+
+    // the Web, named in English and Dutch
+    fn topic() -> Bubble {
+        Bubble::parse_html(
+            r#"<html lang="en"><head><title>Web</title></head><body>
+                <a class="bubble-wikidata-topic" href="https://www.wikidata.org/wiki/Q466">World Wide Web</a>
+                <a class="bubble-wikidata-topic" lang="nl" href="https://www.wikidata.org/wiki/Q466">Web</a>
+                <a class="bubble-wikipedia-topic" href="https://en.wikipedia.org/wiki/HTML">HTML</a>
+            </body></html>"#,
+        )
+        .unwrap()
+    }
+
+    fn query(text: &str) -> Query {
+        Query::parse(text).unwrap()
+    }
+
+    #[test]
+    fn test_topic_names() {
+        let wikidata = Wikidata::read(
+            r#"{"type":"item","id":"Q466","aliases":{"en":[{"language":"en","value":"WWW"},{"language":"en","value":"web"}]}}"#
+                .as_bytes(),
+        )
+        .unwrap();
+        let bubble = topic();
+        let terms =
+            |terms: &[&str]| -> Vec<String> { terms.iter().map(|term| term.to_string()).collect() };
+        assert_eq!(
+            topic_names(bubble.topic(), None),
+            vec![
+                vec![terms(&["html"])],
+                vec![terms(&["world", "wide", "web"]), terms(&["web"])]
+            ]
+        );
+        assert_eq!(
+            topic_names(bubble.topic(), Some(&wikidata))[1],
+            vec![
+                terms(&["world", "wide", "web"]),
+                terms(&["web"]),
+                terms(&["www"])
+            ]
+        );
+    }
+
+    #[test]
+    fn test_expand_query() {
+        let bubble = topic();
+        let topics = topic_names(bubble.topic(), None);
+        assert_eq!(
+            expand_query(&query("web forms"), &topics),
+            query(r#"(web OR "world wide web") forms"#)
+        );
+        assert_eq!(
+            expand_query(&query(r#""World Wide Web" -"web""#), &topics),
+            query(r#"("world wide web" OR web) -("web" OR "world wide web")"#)
+        );
+        // a topic with one name has nothing to add
+        assert_eq!(expand_query(&query("html"), &topics), query("html"));
+        assert_eq!(
+            expand_query(&query("site:web.org world"), &topics),
+            query("site:web.org world")
+        );
+    }
+}
@@ -0,0 +1,227 @@
+use std::{fmt, str::FromStr};
+
+use iri_string::types::{IriAbsoluteStr, IriAbsoluteString};
+
+/// A Wikidata item identifier, like Q8811 for HTML.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Qid(u64);
+
+impl Qid {
+    pub fn new(id: u64) -> Self {
+        Self(id)
+    }
+
+    pub fn id(self) -> u64 {
+        self.0
+    }
+
+    /// The URL we store Wikidata references as.
+    pub fn uri(self) -> IriAbsoluteString {
+        format!("https://www.wikidata.org/wiki/{self}")
+            .try_into()
+            .expect("Wikidata URLs are absolute")
+    }
+}
+
+impl FromStr for Qid {
+    type Err = ();
+
+    fn from_str(qid: &str) -> Result<Self, Self::Err> {
+        let digits = qid
+            .strip_prefix('Q')
+            .or_else(|| qid.strip_prefix('q'))
+            .ok_or(())?;
+        if digits.is_empty()
+            || digits.starts_with('0')
+            || !digits.bytes().all(|b| b.is_ascii_digit())
+        {
+            return Err(());
+        }
+        digits.parse().map(Qid).map_err(|_| ())
+    }
+}
+
+impl fmt::Display for Qid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Q{}", self.0)
+    }
+}
+
+/// The item a Wikidata URL is about, from /wiki/Q…, /entity/Q… or
+/// /wiki/Special:EntityPage/Q….
+pub(super) fn wikidata_qid(uri: &IriAbsoluteStr) -> Option<Qid> {
+    let host = host(uri)?;
+    if !is_http(uri) || host.strip_prefix("m.").unwrap_or(&host) != "wikidata.org" {
+        return None;
+    }
+    let path = uri.path_str();
+    let qid = path
+        .strip_prefix("/wiki/Special:EntityPage/")
+        .or_else(|| path.strip_prefix("/wiki/"))
+        .or_else(|| path.strip_prefix("/entity/"))?;
+    qid.parse().ok()
+}
+
+/// The language code and title of a Wikipedia article URL.
+pub(super) fn wikipedia_article(uri: &IriAbsoluteStr) -> Option<(String, String)> {
+    if !is_http(uri) {
+        return None;
+    }
+    let host = host(uri)?;
+    let lang = host.strip_suffix(".wikipedia.org")?;
+    let lang = lang.strip_suffix(".m").unwrap_or(lang);
+    let valid_lang = !lang.is_empty()
+        && lang.starts_with(|c: char| c.is_ascii_lowercase())
+        && lang.chars().all(|c| c.is_ascii_lowercase() || c == '-');
+    let title = uri.path_str().strip_prefix("/wiki/")?;
+    if !valid_lang || lang == "www" || title.is_empty() {
+        return None;
+    }
+    // browsers copy non-ASCII titles percent-encoded
+    let title = percent_decode(title).unwrap_or_else(|| title.to_string());
+    Some((lang.to_string(), wikipedia_title(&title)))
+}
+
+/// A Wikipedia article title as it appears in canonical URLs: MediaWiki
+/// uses underscores for spaces and capitalizes the first letter.
+pub(super) fn wikipedia_title(title: &str) -> String {
+    let title = title.replace(' ', "_");
+    let mut chars = title.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => title,
+    }
+}
+
+/// The URL we store Wikipedia references as: https on the desktop site,
+/// without query or fragment, the title percent-encoded the way browsers
+/// copy it.
+pub(super) fn wikipedia_uri(lang: &str, title: &str) -> IriAbsoluteString {
+    let mut uri = format!("https://{lang}.wikipedia.org/wiki/");
+    for byte in title.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~!$&'()*+,;=:@/".contains(&byte) {
+            uri.push(byte as char);
+        } else {
+            uri.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    uri.try_into().expect("Wikipedia URLs are absolute")
+}
+
+// None if an escape is malformed or the bytes aren't UTF-8
+fn percent_decode(text: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(text.len());
+    let mut rest = text.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        if byte == b'%' {
+            let hex = std::str::from_utf8(tail.get(..2)?).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            rest = &tail[2..];
+        } else {
+            bytes.push(byte);
+            rest = tail;
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+fn is_http(uri: &IriAbsoluteStr) -> bool {
+    uri.scheme_str().eq_ignore_ascii_case("https") || uri.scheme_str().eq_ignore_ascii_case("http")
+}
+
+// lowercase, without www.
+fn host(uri: &IriAbsoluteStr) -> Option<String> {
+    let host = uri.authority_components()?.host().to_ascii_lowercase();
+    Some(host.strip_prefix("www.").unwrap_or(&host).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn qid(uri: &str) -> Option<Qid> {
+        wikidata_qid(&uri.parse::<IriAbsoluteString>().unwrap())
+    }
+
+    fn article(uri: &str) -> Option<(String, String)> {
+        wikipedia_article(&uri.parse::<IriAbsoluteString>().unwrap())
+    }
+
+    #[test]
+    fn test_qid() {
+        assert_eq!("Q8811".parse(), Ok(Qid::new(8811)));
+        assert_eq!(Qid::new(8811).to_string(), "Q8811");
+        assert_eq!("Q".parse::<Qid>(), Err(()));
+        assert_eq!("Q08811".parse::<Qid>(), Err(()));
+        assert_eq!("P31".parse::<Qid>(), Err(()));
+    }
+
+    #[test]
+    fn test_wikidata_qid() {
+        for uri in [
+            "https://www.wikidata.org/wiki/Q8811",
+            "http://www.wikidata.org/entity/Q8811",
+            "https://m.wikidata.org/wiki/Q8811?uselang=nl",
+            "https://www.wikidata.org/wiki/Special:EntityPage/Q8811",
+        ] {
+            assert_eq!(qid(uri), Some(Qid::new(8811)), "{uri}");
+        }
+        for uri in [
+            "https://example.com/wiki/Q8811",
+            "https://www.wikidata.org/wiki/Property:P31",
+            "https://www.wikidata.org/",
+            "ftp://www.wikidata.org/wiki/Q8811",
+            "https://m.m.wikidata.org/wiki/Q8811",
+        ] {
+            assert_eq!(qid(uri), None, "{uri}");
+        }
+    }
+
+    #[test]
+    fn test_wikipedia_article() {
+        let html = Some(("en".to_string(), "HTML".to_string()));
+        assert_eq!(article("https://en.wikipedia.org/wiki/HTML"), html);
+        assert_eq!(
+            article("http://en.m.wikipedia.org/wiki/HTML?action=view"),
+            html
+        );
+        assert_eq!(article("https://en.m.m.wikipedia.org/wiki/HTML"), None);
+        assert_eq!(
+            article("https://de.wikipedia.org/wiki/hypertext%20Markup_Language"),
+            Some(("de".to_string(), "Hypertext_Markup_Language".to_string()))
+        );
+        assert_eq!(
+            article("https://zh-yue.wikipedia.org/wiki/HTML"),
+            Some(("zh-yue".to_string(), "HTML".to_string()))
+        );
+        // percent-encoded titles are the same article
+        for (encoded, decoded, title) in [
+            ("C%2B%2B", "C++", "C++"),
+            ("Z%C3%BCrich", "Zürich", "Zürich"),
+            ("caf%c3%a9", "café", "Café"),
+        ] {
+            let expected = Some(("de".to_string(), title.to_string()));
+            assert_eq!(
+                article(&format!("https://de.wikipedia.org/wiki/{encoded}")),
+                expected
+            );
+            assert_eq!(
+                article(&format!("https://de.wikipedia.org/wiki/{decoded}")),
+                expected
+            );
+        }
+        // not UTF-8, so taken as it is
+        assert_eq!(
+            article("https://en.wikipedia.org/wiki/100%FF"),
+            Some(("en".to_string(), "100%FF".to_string()))
+        );
+        for uri in [
+            "https://www.wikipedia.org/wiki/HTML",
+            "https://en.wikipedia.org/",
+            "https://en.wikipedia.org/wiki/",
+            "https://example.com/wiki/HTML",
+        ] {
+            assert_eq!(article(uri), None, "{uri}");
+        }
+    }
+}
@@ -0,0 +1,138 @@
+use std::collections::BTreeSet;
+
+use super::{Qid, Topic, Wikidata};
+
+/// How a topic relates to another one in the Wikidata hierarchy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TopicRelation {
+    // both are about the same item
+    Equal,
+    // the other topic is a subclass or part of this one
+    Broader,
+    // a subclass or part of the other topic, like Tokio of Rust
+    Narrower,
+}
+
+impl Wikidata {
+    /// The items a topic's references resolve to.
+    pub fn items(&self, topic: &Topic) -> BTreeSet<Qid> {
+        topic
+            .references()
+            .iter()
+            .filter_map(|reference| self.qid(reference))
+            .collect()
+    }
+
+    /// Whether `narrower` is a subclass or part of `broader`, directly or
+    /// through other items.
+    pub fn is_within(&self, narrower: Qid, broader: Qid) -> bool {
+        // the hierarchy has cycles, so each item is visited once
+        let mut seen = BTreeSet::from([narrower]);
+        let mut pending = vec![narrower];
+        while let Some(qid) = pending.pop() {
+            let Some(entity) = self.entity(qid) else {
+                continue;
+            };
+            for &parent in entity.broader() {
+                if parent == broader {
+                    return true;
+                }
+                if seen.insert(parent) {
+                    pending.push(parent);
+                }
+            }
+        }
+        false
+    }
+
+    /// How `topic` relates to `other`: equal when they share an item,
+    /// narrower when one of its items is within one of the other's.
+    pub fn relation(&self, topic: &Topic, other: &Topic) -> Option<TopicRelation> {
+        let items = self.items(topic);
+        let others = self.items(other);
+        let within = |narrower: &BTreeSet<Qid>, broader: &BTreeSet<Qid>| {
+            narrower
+                .iter()
+                .any(|&item| broader.iter().any(|&parent| self.is_within(item, parent)))
+        };
+        if !items.is_disjoint(&others) {
+            Some(TopicRelation::Equal)
+        } else if within(&items, &others) {
+            Some(TopicRelation::Narrower)
+        } else if within(&others, &items) {
+            Some(TopicRelation::Broader)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use scraper::Html;
+
+    use super::*;
+
+    // Rust, Tokio as a subclass of Rust library, which is part of Rust,
+    // Python, and a cycle between two items
+    const DUMP: &str = r#"[
+{"type":"item","id":"Q575650","labels":{"en":{"language":"en","value":"Rust"}}},
+{"type":"item","id":"Q2","labels":{"en":{"language":"en","value":"Rust library"}},"claims":{"P361":[{"mainsnak":{"snaktype":"value","property":"P361","datavalue":{"value":{"entity-type":"item","numeric-id":575650,"id":"Q575650"},"type":"wikibase-entityid"}}}]}},
+{"type":"item","id":"Q1","labels":{"en":{"language":"en","value":"Tokio"}},"claims":{"P279":[{"mainsnak":{"snaktype":"value","property":"P279","datavalue":{"value":{"entity-type":"item","numeric-id":2,"id":"Q2"},"type":"wikibase-entityid"}}},{"mainsnak":{"snaktype":"somevalue","property":"P279"}}]}},
+{"type":"item","id":"Q28865","labels":{"en":{"language":"en","value":"Python"}}},
+{"type":"item","id":"Q10","claims":{"P279":[{"mainsnak":{"snaktype":"value","property":"P279","datavalue":{"value":{"entity-type":"item","numeric-id":11,"id":"Q11"},"type":"wikibase-entityid"}}}]}},
+{"type":"item","id":"Q11","claims":{"P279":[{"mainsnak":{"snaktype":"value","property":"P279","datavalue":{"value":{"entity-type":"item","numeric-id":10,"id":"Q10"},"type":"wikibase-entityid"}}}]}}
+]"#;
+
+    fn wikidata() -> Wikidata {
+        Wikidata::read(DUMP.as_bytes()).unwrap()
+    }
+
+    fn topic(qids: &[&str]) -> Topic {
+        let anchors = qids
+            .iter()
+            .map(|qid| {
+                format!(
+                    r#"<a class="bubble-wikidata-topic" href="https://www.wikidata.org/wiki/{qid}">{qid}</a>"#
+                )
+            })
+            .collect::<String>();
+        Topic::parse_html(&Html::parse_document(&format!(
+            "<html><body>{anchors}</body></html>"
+        )))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_is_within() {
+        let wikidata = wikidata();
+        let (tokio, library, rust) = (Qid::new(1), Qid::new(2), Qid::new(575650));
+        assert_eq!(wikidata.entity(tokio).unwrap().broader(), &[library]);
+        assert!(wikidata.is_within(tokio, library));
+        assert!(wikidata.is_within(tokio, rust));
+        assert!(!wikidata.is_within(rust, tokio));
+        assert!(!wikidata.is_within(Qid::new(10), rust));
+        assert!(wikidata.is_within(Qid::new(10), Qid::new(11)));
+    }
+
+    #[test]
+    fn test_relation() {
+        let wikidata = wikidata();
+        let rust = topic(&["Q575650"]);
+        let tokio = topic(&["Q1"]);
+        assert_eq!(
+            wikidata.relation(&tokio, &rust),
+            Some(TopicRelation::Narrower)
+        );
+        assert_eq!(
+            wikidata.relation(&rust, &tokio),
+            Some(TopicRelation::Broader)
+        );
+        assert_eq!(
+            wikidata.relation(&rust, &topic(&["Q575650", "Q28865"])),
+            Some(TopicRelation::Equal)
+        );
+        assert_eq!(wikidata.relation(&rust, &topic(&["Q28865"])), None);
+        assert_eq!(wikidata.relation(&rust, &topic(&[])), None);
+    }
+}
@@ -1,7 +1,13 @@
-use scraper::{error::SelectorErrorKind, Html, Selector};
+use iri_string::types::{IriAbsoluteStr, IriAbsoluteString};
+use scraper::{error::SelectorErrorKind, ElementRef, Html, Selector};
 use thiserror::Error;
 
-use super::{Topic, TopicCategory, TopicReference};
+use crate::document::element_lang;
+
+use super::{
+    canonical::{wikidata_qid, wikipedia_article, wikipedia_uri},
+    Label, Topic, TopicCategory, TopicReference,
+};
 
 #[derive(Error, Debug, PartialEq, Eq)]
 pub enum TopicParseError {
@@ -9,6 +15,10 @@ pub enum TopicParseError {
     MissingHref(String),
     #[error("Invalid URL in topic description: {0}")]
     InvalidUrl(String),
+    #[error("Topic without a data-scheme we can recognize: {0}")]
+    UnknownScheme(String),
+    #[error("URL is not a {0} entity or article: {1}")]
+    MismatchedUrl(String, String),
     #[error("Selector error: {0}")]
     SelectorError(String),
 }
@@ -24,25 +34,51 @@ impl Topic {
         let wikipedia_references = Self::make_references(
             html,
             Selector::parse("a.bubble-wikipedia-topic")?,
-            TopicCategory::Wikipedia,
+            |_, _| Ok(TopicCategory::Wikipedia),
         )?;
 
-        let wikidata_references = Self::make_references(
+        let wikidata_references =
+            Self::make_references(html, Selector::parse("a.bubble-wikidata-topic")?, |_, _| {
+                Ok(TopicCategory::Wikidata)
+            })?;
+
+        // any vocabulary, named by `data-scheme` or recognized by its URL
+        let references = Self::make_references(
             html,
-            Selector::parse("a.bubble-wikidata-topic")?,
-            TopicCategory::Wikidata,
+            Selector::parse("a.bubble-topic")?,
+            |description, uri| {
+                match description.value().attr("data-scheme") {
+                    Some(scheme) => TopicCategory::parse_scheme(scheme),
+                    None => TopicCategory::from_uri(uri),
+                }
+                .ok_or_else(|| TopicParseError::UnknownScheme(description.html()))
+            },
         )?;
-        let references = wikipedia_references
+        // links to the same topic name it in more languages
+        let mut merged: Vec<TopicReference> = Vec::new();
+        for reference in wikipedia_references
             .into_iter()
             .chain(wikidata_references)
-            .collect();
-        Ok(Topic { references })
+            .chain(references)
+        {
+            match merged.iter_mut().find(|known| known.uri == reference.uri) {
+                Some(known) => {
+                    for label in reference.labels {
+                        if !known.labels.contains(&label) {
+                            known.labels.push(label);
+                        }
+                    }
+                }
+                None => merged.push(reference),
+            }
+        }
+        Ok(Topic { references: merged })
     }
 
     fn make_references(
         html: &Html,
         selector: Selector,
-        category: TopicCategory,
+        category: impl Fn(ElementRef, &IriAbsoluteStr) -> Result<TopicCategory, TopicParseError>,
     ) -> Result<Vec<TopicReference>, TopicParseError> {
         html.select(&selector)
             .map(|description| {
@@ -50,12 +86,36 @@ impl Topic {
                     .value()
                     .attr("href")
                     .ok_or_else(|| TopicParseError::MissingHref(description.html()))?;
+                // a fragment points into the page, not at another topic
+                let href = href.split('#').next().unwrap_or(href);
+                let uri: IriAbsoluteString = href
+                    .try_into()
+                    .map_err(|_| TopicParseError::InvalidUrl(description.html()))?;
+                let category = category(description, &uri)?;
+                let mismatched = || {
+                    TopicParseError::MismatchedUrl(
+                        category.scheme().to_string(),
+                        description.html(),
+                    )
+                };
+                // Wikipedia and Wikidata have several URLs for the same
+                // thing, keep one
+                let uri = match category {
+                    TopicCategory::Wikidata => wikidata_qid(&uri).ok_or_else(mismatched)?.uri(),
+                    TopicCategory::Wikipedia => {
+                        let (lang, title) = wikipedia_article(&uri).ok_or_else(mismatched)?;
+                        wikipedia_uri(&lang, &title)
+                    }
+                    _ => uri,
+                };
+                let label = Label {
+                    text: description.text().collect(),
+                    lang: element_lang(description),
+                };
                 Ok(TopicReference {
-                    uri: href
-                        .try_into()
-                        .map_err(|_| TopicParseError::InvalidUrl(description.html()))?,
-                    category: category.clone(),
-                    label: description.text().collect(),
+                    uri,
+                    labels: vec![label],
+                    category,
                 })
             })
             .collect::<Result<Vec<_>, TopicParseError>>()
@@ -64,6 +124,7 @@ impl Topic {
 
 #[cfg(test)]
 mod tests {
+    use crate::topic::Qid;
 
     use super::*;
 
@@ -86,7 +147,7 @@ mod tests {
             topic.references[0],
             TopicReference {
                 uri: "https://en.wikipedia.org/wiki/HTML".try_into().unwrap(),
-                label: "HTML".to_string(),
+                labels: vec![Label::new("HTML", None)],
                 category: TopicCategory::Wikipedia,
             }
         );
@@ -111,7 +172,7 @@ mod tests {
             topic.references[0],
             TopicReference {
                 uri: "https://www.wikidata.org/wiki/Q8811".try_into().unwrap(),
-                label: "HTML".to_string(),
+                labels: vec![Label::new("HTML", None)],
                 category: TopicCategory::Wikidata,
             }
         );
@@ -138,18 +199,181 @@ mod tests {
             vec![
                 TopicReference {
                     uri: "https://en.wikipedia.org/wiki/HTML".try_into().unwrap(),
-                    label: "HTML".to_string(),
+                    labels: vec![Label::new("HTML", None)],
                     category: TopicCategory::Wikipedia,
                 },
                 TopicReference {
                     uri: "https://www.wikidata.org/wiki/Q8811".try_into().unwrap(),
-                    label: "HTML".to_string(),
+                    labels: vec![Label::new("HTML", None)],
                     category: TopicCategory::Wikidata,
                 }
             ]
         );
     }
 
+    #[test]
+    fn test_parse_generic_topics() {
+        let html = r#"<html>
+            <body>
+                <a class="bubble-topic" data-scheme="lcsh" href="https://id.loc.gov/authorities/subjects/sh85061211">Hypertext systems</a>
+                <a class="bubble-topic" href="https://openalex.org/C136764020">World Wide Web</a>
+                <a class="bubble-topic" data-scheme="https://schema.org/" href="https://schema.org/WebPage">Web page</a>
+                <a class="bubble-topic" data-scheme="https://vocab.example/terms" href="https://vocab.example/terms/markup">Markup</a>
+            </body>
+        </html>"#;
+        let document = Html::parse_document(html);
+        let topic = Topic::parse_html(&document).unwrap();
+        assert_eq!(
+            topic
+                .references
+                .iter()
+                .map(TopicReference::category)
+                .cloned()
+                .collect::<Vec<_>>(),
+            vec![
+                TopicCategory::Lcsh,
+                TopicCategory::OpenAlex,
+                TopicCategory::SchemaOrg,
+                TopicCategory::Other("https://vocab.example/terms".try_into().unwrap()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_generic_topic_unknown_scheme() {
+        for anchor in [
+            r#"<a class="bubble-topic" href="https://vocab.example/terms/markup">Markup</a>"#,
+            r#"<a class="bubble-topic" data-scheme="mesh" href="https://vocab.example/terms/markup">Markup</a>"#,
+        ] {
+            let document = Html::parse_document(&format!("<html><body>{anchor}</body></html>"));
+            assert_eq!(
+                Topic::parse_html(&document),
+                Err(TopicParseError::UnknownScheme(anchor.to_string()))
+            );
+        }
+    }
+
+    #[test]
+    fn test_scheme_round_trip() {
+        for category in [
+            TopicCategory::Wikipedia,
+            TopicCategory::Wikidata,
+            TopicCategory::Dbpedia,
+            TopicCategory::Lcsh,
+            TopicCategory::OpenAlex,
+            TopicCategory::SchemaOrg,
+            TopicCategory::Other("https://vocab.example/terms".try_into().unwrap()),
+        ] {
+            assert_eq!(
+                TopicCategory::parse_scheme(category.scheme()),
+                Some(category)
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_canonicalizes_references() {
+        let html = r#"<html>
+            <body>
+                <a class="bubble-wikipedia-topic" href="http://en.m.wikipedia.org/wiki/hTML#History">HTML</a>
+                <a class="bubble-wikidata-topic" href="http://www.wikidata.org/entity/Q8811">HTML</a>
+                <a class="bubble-topic" data-scheme="wikidata" href="https://www.wikidata.org/wiki/Special:EntityPage/Q8811">HTML</a>
+            </body>
+        </html>"#;
+        let document = Html::parse_document(html);
+        let topic = Topic::parse_html(&document).unwrap();
+        assert_eq!(
+            topic
+                .references
+                .iter()
+                .map(|reference| reference.uri().as_str())
+                .collect::<Vec<_>>(),
+            vec![
+                "https://en.wikipedia.org/wiki/HTML",
+                "https://www.wikidata.org/wiki/Q8811",
+            ]
+        );
+        // the same item under different URLs is one topic
+        assert_eq!(topic.references[1].labels().len(), 1);
+        assert_eq!(topic.references[1].qid(), Some(Qid::new(8811)));
+        assert_eq!(topic.references[0].qid(), None);
+        assert_eq!(
+            topic.references[0].wikipedia_article(),
+            Some(("en".to_string(), "HTML".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_parse_percent_encoded_wikipedia_titles() {
+        let html = r#"<html>
+            <body>
+                <a class="bubble-wikipedia-topic" href="https://de.wikipedia.org/wiki/Z%C3%BCrich">Zürich</a>
+                <a class="bubble-wikipedia-topic" href="https://de.wikipedia.org/wiki/Zürich">Zürich</a>
+                <a class="bubble-wikipedia-topic" href="https://en.wikipedia.org/wiki/C%2B%2B">C++</a>
+                <a class="bubble-wikipedia-topic" href="https://en.wikipedia.org/wiki/C++">C++</a>
+            </body>
+        </html>"#;
+        let topic = Topic::parse_html(&Html::parse_document(html)).unwrap();
+        assert_eq!(
+            topic
+                .references
+                .iter()
+                .map(|reference| reference.uri().as_str())
+                .collect::<Vec<_>>(),
+            vec![
+                "https://de.wikipedia.org/wiki/Z%C3%BCrich",
+                "https://en.wikipedia.org/wiki/C++",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_mismatched_topic_urls() {
+        for anchor in [
+            r#"<a class="bubble-wikidata-topic" href="https://example.com">HTML</a>"#,
+            r#"<a class="bubble-wikidata-topic" href="https://www.wikidata.org/wiki/Property:P31">HTML</a>"#,
+            r#"<a class="bubble-wikipedia-topic" href="https://www.wikidata.org/wiki/Q8811">HTML</a>"#,
+            r#"<a class="bubble-wikipedia-topic" href="https://en.wikipedia.org/">HTML</a>"#,
+        ] {
+            let document = Html::parse_document(&format!("<html><body>{anchor}</body></html>"));
+            assert!(
+                matches!(
+                    Topic::parse_html(&document),
+                    Err(TopicParseError::MismatchedUrl(_, _))
+                ),
+                "{anchor}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_multilingual_labels() {
+        let html = r#"<html lang="en">
+            <body>
+                <a class="bubble-wikidata-topic" href="https://www.wikidata.org/wiki/Q466">World Wide Web</a>
+                <p lang="nl"><a class="bubble-wikidata-topic" href="https://www.wikidata.org/wiki/Q466">wereldwijd web</a></p>
+                <a class="bubble-wikipedia-topic" hreflang="nl-BE" href="https://nl.wikipedia.org/wiki/World_Wide_Web">web</a>
+                <a class="bubble-topic" href="http://www.wikidata.org/entity/Q466">World Wide Web</a>
+            </body>
+        </html>"#;
+        let document = Html::parse_document(html);
+        let topic = Topic::parse_html(&document).unwrap();
+        assert_eq!(topic.references.len(), 2);
+        let web = &topic.references[1];
+        assert_eq!(
+            web.labels(),
+            &[
+                Label::new("World Wide Web", Some("en")),
+                Label::new("wereldwijd web", Some("nl")),
+            ]
+        );
+        assert_eq!(web.label(), "World Wide Web");
+        assert_eq!(web.label_in("nl"), Some("wereldwijd web"));
+        assert_eq!(web.label_in("fr"), None);
+        assert_eq!(topic.references[0].label_in("nl"), Some("web"));
+        assert_eq!(topic.references[0].label_in("nl-NL"), Some("web"));
+    }
+
     #[test]
     fn test_parse_wikipedia_topic_description_without_href() {
         let html = r#"<html>
@@ -1,5 +1,11 @@
+mod canonical;
+mod hierarchy;
 mod html;
 mod model;
+mod wikidata;
 
+pub use canonical::Qid;
+pub use hierarchy::TopicRelation;
 pub use html::TopicParseError;
-pub use model::{Topic, TopicCategory, TopicReference};
+pub use model::{Label, Topic, TopicCategory, TopicReference};
+pub use wikidata::{Entity, LabelMismatch, Wikidata, WikidataError};
@@ -1,35 +1,215 @@
-use iri_string::types::IriAbsoluteString;
+use iri_string::types::{IriAbsoluteStr, IriAbsoluteString};
+
+use super::canonical::{wikidata_qid, wikipedia_article, Qid};
 
 #[derive(Debug, PartialEq, Eq)]
 pub struct Topic {
     pub(super) references: Vec<TopicReference>,
 }
 
+/// The vocabulary a topic reference is taken from.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum TopicCategory {
     Wikipedia,
     Wikidata,
+    Dbpedia,
+    // Library of Congress Subject Headings
+    Lcsh,
+    OpenAlex,
+    SchemaOrg,
+    // any other vocabulary, such as a SKOS concept scheme, by its IRI
+    Other(IriAbsoluteString),
 }
 
+// short name and scheme IRI of the vocabularies we know
+const SCHEMES: [(TopicCategory, &str, &str); 6] = [
+    (
+        TopicCategory::Wikipedia,
+        "wikipedia",
+        "https://www.wikipedia.org/",
+    ),
+    (
+        TopicCategory::Wikidata,
+        "wikidata",
+        "http://www.wikidata.org/entity/",
+    ),
+    (
+        TopicCategory::Dbpedia,
+        "dbpedia",
+        "http://dbpedia.org/resource/",
+    ),
+    (
+        TopicCategory::Lcsh,
+        "lcsh",
+        "http://id.loc.gov/authorities/subjects",
+    ),
+    (
+        TopicCategory::OpenAlex,
+        "openalex",
+        "https://openalex.org/concepts",
+    ),
+    (
+        TopicCategory::SchemaOrg,
+        "schema.org",
+        "https://schema.org/",
+    ),
+];
+
 #[derive(Debug, PartialEq, Eq)]
 pub struct TopicReference {
     pub(super) uri: IriAbsoluteString,
-    pub(super) label: String,
+    // one per anchor linking to the topic, so in each language the bubble
+    // names it in
+    pub(super) labels: Vec<Label>,
     pub(super) category: TopicCategory,
 }
 
+/// The text of a link, and the language it is in, from `lang` or
+/// `hreflang` on the link or the nearest `lang` around it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Label {
+    pub(super) text: String,
+    pub(super) lang: Option<String>,
+}
+
 impl Topic {
     pub fn references(&self) -> &[TopicReference] {
         &self.references
     }
 }
 
+impl TopicCategory {
+    /// A category from `data-scheme`: the short name of a vocabulary we
+    /// know, or the IRI of any scheme.
+    pub fn parse_scheme(scheme: &str) -> Option<Self> {
+        let scheme = scheme.trim();
+        let known = SCHEMES.iter().find(|(_, name, iri)| {
+            scheme.eq_ignore_ascii_case(name)
+                || scheme.trim_end_matches('/') == iri.trim_end_matches('/')
+        });
+        match known {
+            Some((category, _, _)) => Some(category.clone()),
+            None => IriAbsoluteString::try_from(scheme)
+                .ok()
+                .map(TopicCategory::Other),
+        }
+    }
+
+    /// The vocabulary a topic URL belongs to, judging by its host.
+    pub fn from_uri(uri: &IriAbsoluteStr) -> Option<Self> {
+        let host = uri.authority_components()?.host().to_ascii_lowercase();
+        let on = |domain: &str| host == domain || host.ends_with(&format!(".{domain}"));
+        if on("wikipedia.org") {
+            Some(TopicCategory::Wikipedia)
+        } else if on("wikidata.org") {
+            Some(TopicCategory::Wikidata)
+        } else if on("dbpedia.org") {
+            Some(TopicCategory::Dbpedia)
+        } else if host == "id.loc.gov" && uri.path_str().starts_with("/authorities/subjects") {
+            Some(TopicCategory::Lcsh)
+        } else if on("openalex.org") {
+            Some(TopicCategory::OpenAlex)
+        } else if on("schema.org") {
+            Some(TopicCategory::SchemaOrg)
+        } else {
+            None
+        }
+    }
+
+    /// The short name of a known vocabulary, or the scheme IRI of another.
+    pub fn scheme(&self) -> &str {
+        match self {
+            TopicCategory::Other(scheme) => scheme.as_str(),
+            category => SCHEMES
+                .iter()
+                .find(|(known, _, _)| known == category)
+                .map(|(_, name, _)| *name)
+                .expect("every named category has a scheme"),
+        }
+    }
+}
+
 impl TopicReference {
     pub fn new(uri: IriAbsoluteString, label: String, category: TopicCategory) -> Self {
         Self {
             uri,
-            label,
+            labels: vec![Label::new(&label, None)],
             category,
         }
     }
+
+    pub fn with_label(mut self, label: Label) -> Self {
+        if !self.labels.contains(&label) {
+            self.labels.push(label);
+        }
+        self
+    }
+
+    pub fn uri(&self) -> &IriAbsoluteStr {
+        &self.uri
+    }
+
+    /// The text of the first link to the topic.
+    pub fn label(&self) -> &str {
+        self.labels.first().map_or("", Label::text)
+    }
+
+    pub fn labels(&self) -> &[Label] {
+        &self.labels
+    }
+
+    /// The label in a language, or failing that in another variant of it,
+    /// like "nl-BE" for "nl", or without a language.
+    pub fn label_in(&self, lang: &str) -> Option<&str> {
+        let primary = |lang: &str| lang.split('-').next().unwrap_or(lang).to_ascii_lowercase();
+        let in_lang = |exact: bool| {
+            self.labels.iter().find(move |label| {
+                label.lang().is_some_and(|label_lang| {
+                    label_lang.eq_ignore_ascii_case(lang)
+                        || !exact && primary(label_lang) == primary(lang)
+                })
+            })
+        };
+        in_lang(true)
+            .or_else(|| in_lang(false))
+            .or_else(|| self.labels.iter().find(|label| label.lang.is_none()))
+            .map(Label::text)
+    }
+
+    pub fn category(&self) -> &TopicCategory {
+        &self.category
+    }
+
+    /// The Wikidata item, for Wikidata references.
+    pub fn qid(&self) -> Option<Qid> {
+        match self.category {
+            TopicCategory::Wikidata => wikidata_qid(&self.uri),
+            _ => None,
+        }
+    }
+
+    /// The language code and title, for Wikipedia references.
+    pub fn wikipedia_article(&self) -> Option<(String, String)> {
+        match self.category {
+            TopicCategory::Wikipedia => wikipedia_article(&self.uri),
+            _ => None,
+        }
+    }
+}
+
+impl Label {
+    pub fn new(text: &str, lang: Option<&str>) -> Self {
+        Self {
+            text: text.to_string(),
+            lang: lang.map(str::to_string),
+        }
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn lang(&self) -> Option<&str> {
+        self.lang.as_deref()
+    }
 }
@@ -0,0 +1,445 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::File,
+    io::{BufRead, BufReader},
+    path::Path,
+};
+
+use serde::Deserialize;
+use thiserror::Error;
+
+use super::{
+    canonical::{wikipedia_article, wikipedia_title},
+    Label, Qid, Topic, TopicReference,
+};
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum WikidataError {
+    #[error("Could not read Wikidata dump: {0}")]
+    Io(String),
+    #[error("Invalid entity on line {0} of Wikidata dump: {1}")]
+    Format(usize, String),
+}
+
+impl From<std::io::Error> for WikidataError {
+    fn from(error: std::io::Error) -> Self {
+        WikidataError::Io(error.to_string())
+    }
+}
+
+/// Items from a Wikidata JSON dump, or a subset of one made by keeping only
+/// some of its lines. Everything is looked up locally, so topics can be
+/// checked without a network.
+#[derive(Debug, Default)]
+pub struct Wikidata {
+    entities: BTreeMap<Qid, Entity>,
+    // (language code, article title) to item
+    articles: HashMap<(String, String), Qid>,
+}
+
+/// The names of a Wikidata item, by language code, and the items it is a
+/// subclass or part of.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Entity {
+    labels: BTreeMap<String, String>,
+    aliases: BTreeMap<String, Vec<String>>,
+    pub(super) broader: Vec<Qid>,
+}
+
+/// A topic label that is none of the names of the item it points to.
+#[derive(Debug, PartialEq, Eq)]
+pub struct LabelMismatch<'a> {
+    pub(super) reference: &'a TopicReference,
+    pub(super) label: &'a Label,
+    pub(super) qid: Qid,
+}
+
+// the parts of the dump format we use
+#[derive(Deserialize)]
+struct DumpEntity {
+    id: String,
+    #[serde(default)]
+    labels: HashMap<String, DumpValue>,
+    #[serde(default)]
+    aliases: HashMap<String, Vec<DumpValue>>,
+    #[serde(default)]
+    sitelinks: HashMap<String, DumpSitelink>,
+    #[serde(default)]
+    claims: DumpClaims,
+}
+
+#[derive(Deserialize)]
+struct DumpValue {
+    value: String,
+}
+
+#[derive(Deserialize)]
+struct DumpSitelink {
+    title: String,
+}
+
+#[derive(Deserialize, Default)]
+struct DumpClaims {
+    #[serde(rename = "P279", default)]
+    subclass_of: Vec<DumpClaim>,
+    #[serde(rename = "P361", default)]
+    part_of: Vec<DumpClaim>,
+}
+
+#[derive(Deserialize)]
+struct DumpClaim {
+    mainsnak: DumpSnak,
+}
+
+// no datavalue for "unknown value" and "no value" snaks
+#[derive(Deserialize)]
+struct DumpSnak {
+    datavalue: Option<DumpDataValue>,
+}
+
+#[derive(Deserialize)]
+struct DumpDataValue {
+    value: DumpItem,
+}
+
+#[derive(Deserialize)]
+struct DumpItem {
+    id: String,
+}
+
+impl Wikidata {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn load(path: &Path) -> Result<Self, WikidataError> {
+        Self::read(BufReader::new(File::open(path)?))
+    }
+
+    /// Read a dump: a JSON array with one entity per line. Only items are
+    /// kept; properties and lexemes are skipped.
+    pub fn read(reader: impl BufRead) -> Result<Self, WikidataError> {
+        let mut wikidata = Self::new();
+        for (number, line) in reader.lines().enumerate() {
+            let line = line?;
+            let line = line.trim().trim_end_matches(',');
+            if line.is_empty() || line == "[" || line == "]" {
+                continue;
+            }
+            let entity = serde_json::from_str::<DumpEntity>(line)
+                .map_err(|error| WikidataError::Format(number + 1, error.to_string()))?;
+            wikidata.insert(entity);
+        }
+        Ok(wikidata)
+    }
+
+    fn insert(&mut self, entity: DumpEntity) {
+        let Ok(qid) = entity.id.parse::<Qid>() else {
+            return;
+        };
+        for (site, sitelink) in entity.sitelinks {
+            // enwiki, zh_yuewiki; other projects like enwikiquote don't match
+            if let Some(lang) = site.strip_suffix("wiki") {
+                let lang = lang.replace('_', "-");
+                self.articles
+                    .insert((lang, wikipedia_title(&sitelink.title)), qid);
+            }
+        }
+        let mut broader = Vec::new();
+        for claim in entity
+            .claims
+            .subclass_of
+            .iter()
+            .chain(&entity.claims.part_of)
+        {
+            let item = claim.mainsnak.datavalue.as_ref();
+            if let Some(item) = item.and_then(|item| item.value.id.parse::<Qid>().ok()) {
+                if !broader.contains(&item) {
+                    broader.push(item);
+                }
+            }
+        }
+        self.entities.insert(
+            qid,
+            Entity {
+                labels: entity
+                    .labels
+                    .into_iter()
+                    .map(|(lang, label)| (lang, label.value))
+                    .collect(),
+                aliases: entity
+                    .aliases
+                    .into_iter()
+                    .map(|(lang, aliases)| {
+                        (lang, aliases.into_iter().map(|alias| alias.value).collect())
+                    })
+                    .collect(),
+                broader,
+            },
+        );
+    }
+
+    pub fn entity(&self, qid: Qid) -> Option<&Entity> {
+        self.entities.get(&qid)
+    }
+
+    /// The item a topic reference is about: its own QID for Wikidata
+    /// references, the item linked to the article for Wikipedia ones.
+    pub fn qid(&self, reference: &TopicReference) -> Option<Qid> {
+        reference.qid().or_else(|| {
+            let (lang, title) = wikipedia_article(reference.uri())?;
+            self.articles.get(&(lang, title)).copied()
+        })
+    }
+
+    pub fn resolve(&self, reference: &TopicReference) -> Option<&Entity> {
+        self.entity(self.qid(reference)?)
+    }
+
+    /// Topic labels that don't name the item in their language, or in any
+    /// language when the item has no names in theirs. References to items
+    /// that aren't in the dump aren't checked.
+    pub fn check_labels<'a>(&self, topic: &'a Topic) -> Vec<LabelMismatch<'a>> {
+        let mut mismatches = Vec::new();
+        for reference in topic.references() {
+            let Some(qid) = self.qid(reference) else {
+                continue;
+            };
+            let Some(entity) = self.entity(qid) else {
+                continue;
+            };
+            for label in reference.labels() {
+                if !entity.has_name_in(label.text(), label.lang()) {
+                    mismatches.push(LabelMismatch {
+                        reference,
+                        label,
+                        qid,
+                    });
+                }
+            }
+        }
+        mismatches
+    }
+}
+
+impl Entity {
+    pub fn label(&self, lang: &str) -> Option<&str> {
+        self.labels.get(lang).map(String::as_str)
+    }
+
+    pub fn labels(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.labels
+            .iter()
+            .map(|(lang, label)| (lang.as_str(), label.as_str()))
+    }
+
+    pub fn aliases(&self, lang: &str) -> &[String] {
+        self.aliases
+            .get(lang)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// Labels and aliases in every language.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.labels
+            .values()
+            .chain(self.aliases.values().flatten())
+            .map(String::as_str)
+    }
+
+    /// The items this one is a subclass (P279) or part (P361) of.
+    pub fn broader(&self) -> &[Qid] {
+        &self.broader
+    }
+
+    /// Labels and aliases in a language and its variants, so "en" and
+    /// "en-gb" for "en-GB".
+    pub fn names_in(&self, lang: &str) -> Vec<&str> {
+        let lang = lang.to_ascii_lowercase();
+        let primary = lang.split('-').next().unwrap_or_default();
+        let matches = |code: &String| *code == lang || code == primary;
+        let labels = self
+            .labels
+            .iter()
+            .filter(|(code, _)| matches(code))
+            .map(|(_, label)| label);
+        let aliases = self
+            .aliases
+            .iter()
+            .filter(|(code, _)| matches(code))
+            .flat_map(|(_, aliases)| aliases);
+        labels.chain(aliases).map(String::as_str).collect()
+    }
+
+    pub fn has_name(&self, name: &str) -> bool {
+        let name = normalize(name);
+        self.names().any(|own| normalize(own) == name)
+    }
+
+    /// Whether the name is one of the item's in a language, or in any
+    /// language when it has none in that one.
+    pub fn has_name_in(&self, name: &str, lang: Option<&str>) -> bool {
+        let Some(lang) = lang else {
+            return self.has_name(name);
+        };
+        let names = self.names_in(lang);
+        if names.is_empty() {
+            return self.has_name(name);
+        }
+        let name = normalize(name);
+        names.iter().any(|own| normalize(own) == name)
+    }
+}
+
+// case-folded beyond ASCII, so "Über" matches "über"
+fn normalize(name: &str) -> String {
+    name.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+impl<'a> LabelMismatch<'a> {
+    pub fn reference(&self) -> &'a TopicReference {
+        self.reference
+    }
+
+    pub fn label(&self) -> &'a Label {
+        self.label
+    }
+
+    pub fn qid(&self) -> Qid {
+        self.qid
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use scraper::Html;
+
+    use super::*;
+
+    // HTML and World Wide Web, as a dump subset has them
+    const DUMP: &str = r#"[
+{"type":"item","id":"Q8811","labels":{"en":{"language":"en","value":"HTML"},"nl":{"language":"nl","value":"HTML"}},"aliases":{"en":[{"language":"en","value":"HyperText Markup Language"}]},"sitelinks":{"enwiki":{"site":"enwiki","title":"HTML"},"enwikiquote":{"site":"enwikiquote","title":"Markup"}}},
+{"type":"item","id":"Q466","labels":{"en":{"language":"en","value":"World Wide Web"},"nl":{"language":"nl","value":"wereldwijd web"}},"aliases":{"en":[{"language":"en","value":"WWW"},{"language":"en","value":"the Web"}]},"sitelinks":{"enwiki":{"site":"enwiki","title":"World Wide Web"},"zh_yuewiki":{"site":"zh_yuewiki","title":"萬維網"}}},
+{"type":"property","id":"P31","labels":{"en":{"language":"en","value":"instance of"}}}
+]"#;
+
+    fn wikidata() -> Wikidata {
+        Wikidata::read(DUMP.as_bytes()).unwrap()
+    }
+
+    fn topic(body: &str) -> Topic {
+        Topic::parse_html(&Html::parse_document(&format!(
+            "<html><body>{body}</body></html>"
+        )))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_read() {
+        let wikidata = wikidata();
+        let html = wikidata.entity(Qid::new(8811)).unwrap();
+        assert_eq!(html.label("nl"), Some("HTML"));
+        assert_eq!(html.aliases("en"), &["HyperText Markup Language"]);
+        assert!(html.aliases("nl").is_empty());
+        assert_eq!(wikidata.entities.len(), 2);
+    }
+
+    #[test]
+    fn test_read_invalid() {
+        assert_eq!(
+            Wikidata::read("[\n{\"id\":\n]".as_bytes())
+                .map(|_| ())
+                .map_err(|error| matches!(error, WikidataError::Format(2, _))),
+            Err(true)
+        );
+    }
+
+    #[test]
+    fn test_resolve_wikipedia_article() {
+        let wikidata = wikidata();
+        let topic = topic(
+            r#"<a class="bubble-wikipedia-topic" href="https://en.wikipedia.org/wiki/World_Wide_Web">Web</a>
+               <a class="bubble-wikipedia-topic" href="https://zh-yue.wikipedia.org/wiki/萬維網">Web</a>
+               <a class="bubble-wikipedia-topic" href="https://en.wikipedia.org/wiki/Markup">Markup</a>
+               <a class="bubble-wikidata-topic" href="https://www.wikidata.org/wiki/Q8811">HTML</a>"#,
+        );
+        let qids = topic
+            .references()
+            .iter()
+            .map(|reference| wikidata.qid(reference))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            qids,
+            vec![
+                Some(Qid::new(466)),
+                Some(Qid::new(466)),
+                None,
+                Some(Qid::new(8811))
+            ]
+        );
+        let web = wikidata.resolve(&topic.references()[0]).unwrap();
+        assert_eq!(web.label("nl"), Some("wereldwijd web"));
+    }
+
+    #[test]
+    fn test_resolve_percent_encoded_wikipedia_article() {
+        let wikidata = wikidata();
+        // 萬維網 as browsers copy it
+        let topic = topic(
+            r#"<a class="bubble-wikipedia-topic" href="https://zh-yue.wikipedia.org/wiki/%E8%90%AC%E7%B6%AD%E7%B6%B2">Web</a>
+               <a class="bubble-wikipedia-topic" href="https://en.wikipedia.org/wiki/World%20Wide%20Web">Web</a>"#,
+        );
+        for reference in topic.references() {
+            assert_eq!(wikidata.qid(reference), Some(Qid::new(466)));
+        }
+    }
+
+    #[test]
+    fn test_check_labels() {
+        let wikidata = wikidata();
+        let topic = topic(
+            r#"<a class="bubble-wikipedia-topic" href="https://en.wikipedia.org/wiki/World_Wide_Web">the   web</a>
+               <a class="bubble-wikidata-topic" href="https://www.wikidata.org/wiki/Q466">Wereldwijd web</a>
+               <a class="bubble-wikidata-topic" href="https://www.wikidata.org/wiki/Q8811">CSS</a>
+               <a class="bubble-wikidata-topic" href="https://www.wikidata.org/wiki/Q42">Unknown</a>"#,
+        );
+        let mismatches = wikidata.check_labels(&topic);
+        assert_eq!(mismatches.len(), 1);
+        assert_eq!(mismatches[0].reference().label(), "CSS");
+        assert_eq!(mismatches[0].qid(), Qid::new(8811));
+    }
+
+    #[test]
+    fn test_check_labels_in_their_language() {
+        let wikidata = wikidata();
+        let topic = topic(
+            r#"<a class="bubble-wikidata-topic" lang="nl-BE" href="https://www.wikidata.org/wiki/Q466">wereldwijd web</a>
+               <a class="bubble-wikidata-topic" lang="nl" href="https://www.wikidata.org/wiki/Q466">WWW</a>
+               <a class="bubble-wikidata-topic" lang="fr" href="https://www.wikidata.org/wiki/Q466">World Wide Web</a>"#,
+        );
+        let mismatches = wikidata.check_labels(&topic);
+        assert_eq!(mismatches.len(), 1);
+        assert_eq!(mismatches[0].label().text(), "WWW");
+        assert_eq!(mismatches[0].label().lang(), Some("nl"));
+    }
+
+    #[test]
+    fn test_has_name_ignores_non_ascii_case() {
+        let wikidata = Wikidata::read(
+            r#"[
+{"type":"item","id":"Q1","labels":{"de":{"language":"de","value":"Über"}},"aliases":{"el":[{"language":"el","value":"Σχετικά"}]}}
+]"#
+            .as_bytes(),
+        )
+        .unwrap();
+        let entity = wikidata.entity(Qid::new(1)).unwrap();
+        assert!(entity.has_name("über"));
+        assert!(entity.has_name_in("ÜBER", Some("de")));
+        assert!(entity.has_name("σχετικά"));
+        assert!(!entity.has_name("uber"));
+    }
+}